@@ -68,6 +68,10 @@ impl Command {
         // debugging purposes. We don't want child processes to inherit it.
         cmd.env_remove("RUST_LOG");
 
+        if self.opts.no_agent {
+            cmd.env_remove("SSH_AUTH_SOCK");
+        }
+
         // Have the shell assume it's a login shell.
         cmd.arg("-l");
 
@@ -3,16 +3,25 @@ use clap::Args;
 use log::{debug, info, warn};
 use nix::sys::{
     inotify::{AddWatchFlags, InitFlags, Inotify},
-    wait::{WaitStatus, waitpid},
+    signal::{SigSet, Signal},
+    wait::{WaitPidFlag, WaitStatus, waitpid},
 };
 use std::path::Path;
 
 /// Wait for the Litterbox to finish (for internal use)
 #[derive(Args, Debug)]
-pub struct Command {}
+pub struct Command {
+    /// Keep running after the last session ends (until the container is stopped)
+    #[arg(long)]
+    keep_running: bool,
+}
 
 impl Command {
     pub fn run(self) -> Result<()> {
+        if self.keep_running {
+            return keep_running();
+        }
+
         let session_lock_path = Path::new("/session.lock");
         let inotify = Inotify::init(InitFlags::empty())?;
         inotify.add_watch(session_lock_path, AddWatchFlags::IN_MODIFY)?;
@@ -71,3 +80,36 @@ impl Command {
         Ok(())
     }
 }
+
+/// Reaps child processes until the container gets asked to stop. Used for
+/// non-interactive Litterboxes that host long-running services.
+fn keep_running() -> Result<()> {
+    let mut signals = SigSet::empty();
+    signals.add(Signal::SIGCHLD);
+    signals.add(Signal::SIGINT);
+    signals.add(Signal::SIGTERM);
+    signals.thread_block()?;
+
+    info!("Litterbox has started and will keep running until stopped");
+
+    loop {
+        match signals.wait()? {
+            Signal::SIGCHLD => loop {
+                match waitpid(None, Some(WaitPidFlag::WNOHANG)) {
+                    Ok(WaitStatus::StillAlive) | Err(nix::errno::Errno::ECHILD) => break,
+                    Ok(status) => debug!("Child process finished: {status:?}"),
+                    Err(cause) => bail!(cause),
+                }
+            },
+
+            signal => {
+                debug!("Received {signal}, stopping");
+                break;
+            }
+        }
+    }
+
+    info!("Litterbox has finished");
+
+    Ok(())
+}
@@ -1,11 +1,24 @@
-use anyhow::Result;
-use futures::Future;
+use anyhow::{Context as _, Result};
+use futures::channel::oneshot;
+use futures::{Future, StreamExt};
 use russh::keys::*;
-use std::path::PathBuf;
-use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use strum_macros::{Display, EnumString};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::UnixStream;
 use tokio::process::Command;
+use tokio::task::JoinHandle;
+use tracing::Instrument;
 
 use crate::files::SshSockFile;
 use crate::{
@@ -13,7 +26,19 @@ use crate::{
     utils::{extract_stdout, trace_arguments},
 };
 
-#[derive(PartialEq, Eq, Hash, Display, Clone, Copy, EnumString)]
+/// Lists the identities of the host's SSH agent from `SSH_AUTH_SOCK`
+pub async fn host_agent_identities() -> Result<Vec<agent::AgentIdentity>> {
+    let mut client = agent::client::AgentClient::connect_env()
+        .await
+        .context("Failed to connect to the SSH agent at SSH_AUTH_SOCK")?;
+
+    client
+        .request_identities()
+        .await
+        .context("Failed to list the keys of the SSH agent")
+}
+
+#[derive(PartialEq, Eq, Hash, Display, Clone, Copy, EnumString, Debug, Serialize, Deserialize)]
 pub enum UserRequest {
     RequestKeys,
     AddKeys,
@@ -40,6 +65,42 @@ impl From<agent::server::MessageType> for UserRequest {
     }
 }
 
+/// How much harm approving a request could do, used to style the dialog
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Only reveals public information
+    Low,
+    /// Changes the state of the agent
+    Medium,
+    /// Uses a key on your behalf or removes keys
+    High,
+}
+
+impl UserRequest {
+    pub fn severity(&self) -> Severity {
+        match self {
+            UserRequest::RequestKeys => Severity::Low,
+            UserRequest::AddKeys | UserRequest::Lock | UserRequest::Unlock => Severity::Medium,
+            UserRequest::Sign | UserRequest::RemoveKeys | UserRequest::RemoveAllKeys => {
+                Severity::High
+            }
+        }
+    }
+
+    /// Explains what approving the request allows
+    pub fn description(&self) -> &'static str {
+        match self {
+            UserRequest::RequestKeys => "List the public keys held by the agent.",
+            UserRequest::AddKeys => "Add a key to the agent.",
+            UserRequest::RemoveKeys => "Remove a key from the agent.",
+            UserRequest::RemoveAllKeys => "Remove all keys from the agent.",
+            UserRequest::Sign => "Use one of your keys to authenticate as you.",
+            UserRequest::Lock => "Lock the agent.",
+            UserRequest::Unlock => "Unlock the agent.",
+        }
+    }
+}
+
 #[derive(Debug, EnumString, Display)]
 pub enum UserResponse {
     Approved,
@@ -47,12 +108,132 @@ pub enum UserResponse {
     ApprovedForSession,
 }
 
+/// How the agent decided on a request, as recorded in the audit log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+enum Decision {
+    /// The agent was not locked (e.g. while registering keys)
+    AutoApproved,
+    /// Signing was approved without prompting, within the rate limit
+    AutoApprovedSign,
+    ApprovedForSession,
+    UserApproved,
+    UserDeclined,
+    DeclinedByPolicy,
+    Failed,
+}
+
+impl Decision {
+    fn approved(self) -> bool {
+        matches!(
+            self,
+            Decision::AutoApproved
+                | Decision::AutoApprovedSign
+                | Decision::ApprovedForSession
+                | Decision::UserApproved
+        )
+    }
+}
+
+#[derive(Serialize)]
+struct AuditRecord<'a> {
+    timestamp: u64,
+    litterbox: &'a str,
+    request: UserRequest,
+    /// The keys a signature could have been made with. The agent is not told
+    /// which key gets used.
+    key_fingerprints: Vec<String>,
+    decision: Decision,
+}
+
+/// Restricts which requests the keys attached to a Litterbox may be used for.
+/// Keys without an entry are unrestricted.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KeyPolicy(pub HashMap<String, Vec<UserRequest>>);
+
+impl KeyPolicy {
+    pub fn allows(&self, key_name: &str, request: UserRequest) -> bool {
+        self.0
+            .get(key_name)
+            .is_none_or(|allowed| allowed.contains(&request))
+    }
+
+    /// The requests that are allowed by at least one of the given keys, or
+    /// `None` if any of them is unrestricted.
+    ///
+    /// The agent cannot tell which key a request targets (apart from signing
+    /// in `confirm`), so this is what gets enforced per request.
+    pub fn allowed_requests<'a>(
+        &self,
+        key_names: impl IntoIterator<Item = &'a str>,
+    ) -> Option<HashSet<UserRequest>> {
+        let mut allowed = HashSet::new();
+
+        for key_name in key_names {
+            allowed.extend(self.0.get(key_name)?.iter().copied());
+        }
+
+        Some(allowed)
+    }
+}
+
+/// Approves signing requests without prompting, up to `per_minute` of them
+/// within any minute when a limit is set.
+pub struct SignAutoApproval {
+    per_minute: Option<u32>,
+    recent: Mutex<VecDeque<Instant>>,
+}
+
+impl SignAutoApproval {
+    const WINDOW: Duration = Duration::from_secs(60);
+
+    pub fn new(per_minute: Option<u32>) -> Self {
+        Self {
+            per_minute,
+            recent: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Counts a signature against the limit if there is room for it
+    fn try_approve(&self, now: Instant) -> bool {
+        let Some(limit) = self.per_minute else {
+            return true;
+        };
+
+        let mut recent = self.recent.lock().expect("Lock should not be poisoned");
+        while recent
+            .front()
+            .is_some_and(|approved| now.duration_since(*approved) >= Self::WINDOW)
+        {
+            recent.pop_front();
+        }
+
+        if recent.len() < limit as usize {
+            recent.push_back(now);
+            true
+        } else {
+            false
+        }
+    }
+}
+
 pub struct AgentState {
     /// When the agent is locked, users will need to approve requests
     pub locked: AtomicBool,
 
     /// When set, users no longer need to approve requests to list keys
     pub approved_for_session: AtomicBool,
+
+    /// Requests that may be approved (all of them when `None`)
+    pub allowed_requests: Option<HashSet<UserRequest>>,
+
+    /// Registered keys and their names. Only keys that may sign get registered.
+    pub signing_keys: Vec<(PublicKey, String)>,
+
+    /// Where decisions get appended to, if auditing is enabled
+    pub audit_log: Option<PathBuf>,
+
+    /// When set, signing requests are approved without prompting
+    pub auto_approve_signs: Option<SignAutoApproval>,
 }
 
 impl Default for AgentState {
@@ -60,6 +241,10 @@ impl Default for AgentState {
         Self {
             locked: AtomicBool::new(false),
             approved_for_session: AtomicBool::new(false),
+            allowed_requests: None,
+            signing_keys: Vec::new(),
+            audit_log: None,
+            auto_approve_signs: None,
         }
     }
 }
@@ -71,30 +256,76 @@ struct AskAgent {
     agent_state: Arc<AgentState>,
 }
 
-impl agent::server::Agent for AskAgent {
-    fn confirm(
-        self,
-        _: std::sync::Arc<PrivateKey>,
-    ) -> Box<dyn Future<Output = (Self, bool)> + Send + Unpin> {
-        todo!("Confirm private key")
+impl AskAgent {
+    fn audit(&self, request: UserRequest, decision: Decision) {
+        let Some(path) = &self.agent_state.audit_log else {
+            return;
+        };
+
+        let record = AuditRecord {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            litterbox: &self.lbx_name,
+            request,
+            key_fingerprints: if request == UserRequest::Sign {
+                self.agent_state
+                    .signing_keys
+                    .iter()
+                    .map(|(key, _)| key.fingerprint(HashAlg::Sha256).to_string())
+                    .collect()
+            } else {
+                Vec::new()
+            },
+            decision,
+        };
+
+        let result = serde_json::to_string(&record)
+            .map_err(io::Error::from)
+            .and_then(|line| {
+                let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+                writeln!(file, "{line}")
+            });
+
+        if let Err(e) = result {
+            log::error!("Failed to write to audit log {path:?}: {e}");
+        }
     }
 
-    async fn confirm_request(&self, msg: agent::server::MessageType) -> bool {
-        let request: UserRequest = msg.into();
+    async fn decide(&self, request: UserRequest) -> Decision {
+        // The key policy applies whether or not the agent is locked
+        if let Some(allowed) = &self.agent_state.allowed_requests
+            && !allowed.contains(&request)
+        {
+            log::warn!("Key policy does not allow request, declining: {request}");
+            return Decision::DeclinedByPolicy;
+        }
 
         if !self.agent_state.locked.load(Ordering::SeqCst) {
             log::debug!(
                 "Agent not locked, request automatically approved: {}",
                 request
             );
-            return true;
+            return Decision::AutoApproved;
         }
 
         if request == UserRequest::RequestKeys
             && self.agent_state.approved_for_session.load(Ordering::SeqCst)
         {
-            log::info!("RequestKeys approved for session, not prompting.");
-            return true;
+            log::debug!("RequestKeys approved for session, not prompting.");
+            return Decision::ApprovedForSession;
+        }
+
+        if request == UserRequest::Sign
+            && let Some(auto_approval) = &self.agent_state.auto_approve_signs
+        {
+            if auto_approval.try_approve(Instant::now()) {
+                log::debug!("Sign request automatically approved.");
+                return Decision::AutoApprovedSign;
+            }
+
+            log::info!("Automatic sign approval limit reached, prompting.");
         }
 
         let mut cmd = Command::new(&self.litterbox_path);
@@ -105,9 +336,12 @@ impl agent::server::Agent for AskAgent {
             "--lbx-name",
             &self.lbx_name,
         ]);
-        trace_arguments(cmd.as_std());
+        // The span is attached to the future since it cannot be held across
+        // the await
+        let span = trace_arguments(cmd.as_std()).exit();
         let output = cmd
             .output()
+            .instrument(span)
             .await
             .expect("Litterbox should return valid output to itself.");
 
@@ -119,52 +353,288 @@ impl agent::server::Agent for AskAgent {
 
         if let Ok(response) = response.parse() {
             match response {
-                UserResponse::Approved => true,
-                UserResponse::Declined => false,
+                UserResponse::Approved => Decision::UserApproved,
+                UserResponse::Declined => Decision::UserDeclined,
                 UserResponse::ApprovedForSession => {
                     self.agent_state
                         .approved_for_session
                         .store(true, Ordering::SeqCst);
 
-                    true
+                    Decision::UserApproved
                 }
             }
         } else {
             log::error!("Unexpected confirmation response '{response}'");
 
-            false
+            Decision::Failed
         }
     }
 }
 
-pub async fn start_ssh_agent(lbx_name: &str, agent_state: Arc<AgentState>) -> Result<PathBuf> {
+impl agent::server::Agent for AskAgent {
+    fn confirm(
+        self,
+        key: std::sync::Arc<PrivateKey>,
+    ) -> Box<dyn Future<Output = (Self, bool)> + Send + Unpin> {
+        let public_key = key.public_key();
+        let may_sign = match self
+            .agent_state
+            .signing_keys
+            .iter()
+            .find(|(registered, _)| registered.key_data() == public_key.key_data())
+        {
+            Some((_, name)) => {
+                log::debug!("Signing with key: {name}");
+                true
+            }
+            None => {
+                log::warn!("Declining to sign with a key that was not registered by Litterbox");
+                false
+            }
+        };
+
+        Box::new(futures::future::ready((self, may_sign)))
+    }
+
+    async fn confirm_request(&self, msg: agent::server::MessageType) -> bool {
+        let request: UserRequest = msg.into();
+        let decision = self.decide(request).await;
+
+        self.audit(request, decision);
+        decision.approved()
+    }
+}
+
+/// How long in-flight requests get to finish when the agent shuts down
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// A client connection to the agent. Every connection holds a clone of the
+/// same `Arc` so that the open ones can be counted during shutdown.
+struct TrackedConnection {
+    stream: UnixStream,
+    _guard: Arc<()>,
+}
+
+impl AsyncRead for TrackedConnection {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TrackedConnection {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+/// A running SSH agent. Dropping it without calling `shutdown` leaves the
+/// server running until the runtime stops.
+pub struct AgentHandle {
+    stop_accepting: oneshot::Sender<()>,
+    server: JoinHandle<()>,
+    connections: Arc<()>,
+    ssh_sock: SshSockFile,
+}
+
+impl AgentHandle {
+    pub fn path(&self) -> &Path {
+        self.ssh_sock.path()
+    }
+
+    /// Stops accepting new connections, gives the open ones time to finish
+    /// their requests (e.g. a signature for a `git push`) and only then
+    /// removes the socket file.
+    pub async fn shutdown(self) {
+        log::debug!("Shutting down SSH agent");
+
+        // The receiver is gone if the server already stopped by itself
+        let _ = self.stop_accepting.send(());
+        if let Err(e) = self.server.await {
+            log::error!("SSH agent server task failed: {e}");
+        }
+
+        let deadline = Instant::now() + SHUTDOWN_GRACE_PERIOD;
+        loop {
+            let open = Arc::strong_count(&self.connections) - 1;
+            if open == 0 {
+                break;
+            }
+
+            if Instant::now() >= deadline {
+                log::warn!("Closing SSH agent with {open} connection(s) still open");
+                break;
+            }
+
+            log::debug!("Waiting for {open} SSH agent connection(s) to finish");
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        drop(self.ssh_sock);
+        log::debug!("SSH agent has been shut down");
+    }
+}
+
+pub async fn start_ssh_agent(lbx_name: &str, agent_state: Arc<AgentState>) -> Result<AgentHandle> {
     let litterbox_path = litterbox_binary_path();
 
     let ssh_sock = SshSockFile::new(lbx_name, false)?;
-    let agent_path = ssh_sock.path().to_owned();
 
     let ssh_sock_path = ssh_sock.path();
     log::debug!("Binding SSH socket: {:#?}", ssh_sock_path);
     let listener =
         tokio::net::UnixListener::bind(ssh_sock_path).expect("SSH socket should be bindable");
 
+    let (stop_accepting, stopped) = oneshot::channel();
+    let connections = Arc::new(());
+    let guard = connections.clone();
+    let incoming = tokio_stream::wrappers::UnixListenerStream::new(listener)
+        .take_until(stopped)
+        .map(move |stream| {
+            stream.map(|stream| TrackedConnection {
+                stream,
+                _guard: guard.clone(),
+            })
+        });
+
     let lbx_name = lbx_name.to_string();
-    tokio::spawn(async move {
+    let server = tokio::spawn(async move {
         log::debug!("Starting SSH agent server task");
 
-        // We need to keep the socket object alive to prevent the file from getting deleted
-        let _ssh_sock = ssh_sock;
-
-        russh::keys::agent::server::serve(
-            tokio_stream::wrappers::UnixListenerStream::new(listener),
+        // Connections that were already accepted keep being served by their
+        // own tasks after this returns.
+        let result = russh::keys::agent::server::serve(
+            incoming,
             AskAgent {
                 lbx_name,
                 litterbox_path,
                 agent_state,
             },
         )
-        .await
+        .await;
+
+        if let Err(e) = result {
+            log::error!("SSH agent server stopped with an error: {e}");
+        }
     });
 
-    Ok(agent_path)
+    Ok(AgentHandle {
+        stop_accepting,
+        server,
+        connections,
+        ssh_sock,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_policy_restricts_requests() {
+        let policy = KeyPolicy(HashMap::from([
+            (
+                "deploy".to_owned(),
+                vec![UserRequest::RequestKeys, UserRequest::Sign],
+            ),
+            ("ci".to_owned(), vec![UserRequest::RequestKeys]),
+        ]));
+
+        assert!(policy.allows("deploy", UserRequest::Sign));
+        assert!(!policy.allows("ci", UserRequest::Sign));
+        assert!(policy.allows("other", UserRequest::RemoveAllKeys));
+
+        let allowed = policy.allowed_requests(["deploy", "ci"]).unwrap();
+        assert!(allowed.contains(&UserRequest::Sign));
+        assert!(!allowed.contains(&UserRequest::AddKeys));
+
+        assert!(policy.allowed_requests(["deploy", "other"]).is_none());
+    }
+
+    #[test]
+    fn key_policy_applies_to_unlocked_agents() {
+        let agent = AskAgent {
+            lbx_name: "test".to_owned(),
+            litterbox_path: PathBuf::new(),
+            agent_state: Arc::new(AgentState {
+                allowed_requests: Some(HashSet::from([UserRequest::RequestKeys])),
+                ..Default::default()
+            }),
+        };
+
+        assert_eq!(
+            futures::executor::block_on(agent.decide(UserRequest::Sign)),
+            Decision::DeclinedByPolicy
+        );
+        assert_eq!(
+            futures::executor::block_on(agent.decide(UserRequest::RequestKeys)),
+            Decision::AutoApproved
+        );
+    }
+
+    #[test]
+    fn signing_and_removal_are_high_severity() {
+        assert_eq!(UserRequest::RequestKeys.severity(), Severity::Low);
+        assert_eq!(UserRequest::Sign.severity(), Severity::High);
+        assert_eq!(UserRequest::RemoveAllKeys.severity(), Severity::High);
+        assert!(UserRequest::AddKeys.severity() < Severity::High);
+    }
+
+    #[test]
+    fn sign_auto_approval_is_rate_limited() {
+        let start = Instant::now();
+        let auto_approval = SignAutoApproval::new(Some(2));
+
+        assert!(auto_approval.try_approve(start));
+        assert!(auto_approval.try_approve(start + Duration::from_secs(10)));
+        assert!(!auto_approval.try_approve(start + Duration::from_secs(20)));
+        assert!(auto_approval.try_approve(start + Duration::from_secs(61)));
+
+        let unlimited = SignAutoApproval::new(None);
+        assert!((0..100).all(|_| unlimited.try_approve(start)));
+    }
+
+    #[test]
+    fn audit_log_records_decisions() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("test.audit.jsonl");
+
+        let agent = AskAgent {
+            lbx_name: "test".to_owned(),
+            litterbox_path: PathBuf::new(),
+            agent_state: Arc::new(AgentState {
+                audit_log: Some(path.clone()),
+                ..Default::default()
+            }),
+        };
+        agent.audit(UserRequest::RequestKeys, Decision::UserApproved);
+        agent.audit(UserRequest::Sign, Decision::DeclinedByPolicy);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+
+        let records: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["litterbox"], "test");
+        assert_eq!(records[0]["decision"], "UserApproved");
+        assert_eq!(records[1]["request"], "Sign");
+        assert_eq!(records[1]["decision"], "DeclinedByPolicy");
+    }
 }
@@ -1,20 +1,227 @@
-use anyhow::Result;
+use anyhow::{Result, ensure};
 use clap::Args;
+use inquire::Confirm;
+use log::warn;
+use std::{fs, path::PathBuf};
 
-use crate::podman::{build_image, build_litterbox};
+use crate::{
+    devices::{attach_devices_on_build, parse_device_path, split_build_devices},
+    files::{dir_size, lbx_home_path},
+    keys::Keys,
+    podman::{
+        ContainerState, build_image, build_litterbox, dockerfile_needs_user_password,
+        get_container, import_image, parse_annotation, parse_user_label, replace_litterbox,
+    },
+    settings::LitterboxSettings,
+    utils::{assume_yes, ensure_interactive, format_size},
+};
 
 /// Build a new Litterbox
 #[derive(Args, Debug)]
 pub struct Command {
     /// The name of the Litterbox to build
     name: String,
+
+    /// Additional label to attach to the image and container (can be repeated)
+    #[arg(long = "label", value_name = "KEY=VALUE", value_parser = parse_user_label)]
+    labels: Vec<String>,
+
+    /// Set the password of the container user from a secret stored with `keys add-secret`
+    #[arg(long, value_name = "SECRET_NAME")]
+    password_from: Option<String>,
+
+    /// Rebuild the image and replace an existing container without asking,
+    /// stopping it first if it is running
+    #[arg(long)]
+    force: bool,
+
+    /// Build from this base image (tag or digest) instead of the Dockerfile's
+    /// default, and keep using it for later builds
+    #[arg(long, value_name = "IMAGE", value_parser = parse_image_ref)]
+    base_image: Option<String>,
+
+    /// Use an existing image (e.g. a published dev image) instead of
+    /// building one from the Dockerfile
+    #[arg(
+        long,
+        value_name = "IMAGE",
+        value_parser = parse_image_ref,
+        conflicts_with_all = ["base_image", "password_from"]
+    )]
+    image: Option<String>,
+
+    /// Start the home directory out as a copy of another Litterbox's
+    #[arg(long, value_name = "LBX_NAME")]
+    copy_from: Option<String>,
+
+    /// OCI annotation to create the container with, which is kept for later
+    /// builds (can be repeated)
+    #[arg(long = "annotation", value_name = "KEY=VALUE", value_parser = parse_annotation)]
+    annotations: Vec<(String, String)>,
+
+    /// Attach a host device once the Litterbox is built (can be repeated)
+    #[arg(long = "attach-device", value_name = "PATH", value_parser = parse_device_path)]
+    attach_devices: Vec<String>,
+}
+
+/// Rejects image references that could not be passed on to podman as is
+fn parse_image_ref(input: &str) -> Result<String> {
+    ensure!(
+        !input.is_empty() && !input.contains(char::is_whitespace),
+        "\"{input}\" is not a valid image reference"
+    );
+
+    Ok(input.to_owned())
 }
 
 impl Command {
     pub fn run(self) -> Result<()> {
-        build_image(&self.name)?;
-        build_litterbox(&self.name)?;
+        if self.force && !self.confirm_force()? {
+            eprintln!("Okay, the Litterbox won't be rebuilt!");
+            return Ok(());
+        }
+
+        // Checked before building so that a refusal does not waste a build
+        let copy_from = match &self.copy_from {
+            Some(src_name) => match self.confirm_copy_from(src_name)? {
+                Some(src_home) => Some(src_home),
+                None => {
+                    eprintln!("Okay, the Litterbox won't be built!");
+                    return Ok(());
+                }
+            },
+            None => None,
+        };
+
+        // Avoid asking for the keys password when the secret would go unused
+        let user_password = match &self.password_from {
+            Some(_) if !dockerfile_needs_user_password(&self.name)? => {
+                warn!(
+                    "The Dockerfile of '{}' does not use the user_password build secret. \
+                     Ignoring --password-from.",
+                    self.name
+                );
+                None
+            }
+            Some(secret_name) => Some(Keys::load()?.reveal_secret(secret_name)?),
+            None => None,
+        };
+
+        let image_id = match &self.image {
+            Some(image_ref) => import_image(&self.name, image_ref, &self.labels)?,
+            None => build_image(
+                &self.name,
+                &self.labels,
+                user_password.as_deref(),
+                self.base_image.as_deref(),
+                self.force,
+            )?,
+        };
+
+        // Devices that can be passed through avoid asking for root permissions
+        let (passthrough, needs_node) = split_build_devices(&self.attach_devices);
+        if self.force {
+            replace_litterbox(
+                &self.name,
+                &image_id,
+                &self.labels,
+                &passthrough,
+                &self.annotations,
+                copy_from.as_deref(),
+            )?;
+        } else {
+            build_litterbox(
+                &self.name,
+                &image_id,
+                &self.labels,
+                &passthrough,
+                &self.annotations,
+                copy_from.as_deref(),
+            )?;
+        }
+
+        // The settings only exist once the Litterbox has been built
+        if let Some(base_image) = &self.base_image
+            && let Some(mut settings) = LitterboxSettings::load(&self.name)?
+        {
+            settings.record_base_image(&self.name, base_image)?;
+        }
+        attach_devices_on_build(&self.name, &needs_node)?;
 
         Ok(())
     }
+
+    /// Checks that the home directory of `src_name` can be copied into the
+    /// (empty) one of the new Litterbox and asks whether its size is okay.
+    /// Returns the home directory to copy unless the user declined.
+    fn confirm_copy_from(&self, src_name: &str) -> Result<Option<PathBuf>> {
+        ensure!(
+            src_name != self.name,
+            "A Litterbox cannot copy its own home directory"
+        );
+
+        let src_home = lbx_home_path(src_name)?;
+        ensure!(
+            src_home.is_dir(),
+            "Litterbox '{src_name}' has no home directory at {src_home:?} to copy"
+        );
+
+        let dest_home = lbx_home_path(&self.name)?;
+        let dest_is_empty = match fs::read_dir(&dest_home) {
+            Ok(mut entries) => entries.next().is_none(),
+            Err(_) => !dest_home.exists(),
+        };
+        ensure!(
+            dest_is_empty,
+            "The home directory {dest_home:?} of '{}' is not empty, so nothing gets copied into it",
+            self.name
+        );
+
+        let size = format_size(dir_size(&src_home));
+        if assume_yes() {
+            return Ok(Some(src_home));
+        }
+
+        ensure_interactive(
+            "Confirming the copy",
+            "Pass --yes to confirm the copy up front.",
+        )?;
+        let should_copy = Confirm::new(&format!(
+            "Copy the home directory of '{src_name}' ({size}) into the new Litterbox?"
+        ))
+        .with_default(true)
+        .with_help_message("Sockets and device nodes are left out.")
+        .prompt()?;
+
+        Ok(should_copy.then_some(src_home))
+    }
+
+    /// Asks before a forced rebuild throws away an existing container.
+    fn confirm_force(&self) -> Result<bool> {
+        let Some(container) = get_container(&self.name)? else {
+            return Ok(true);
+        };
+
+        if assume_yes() {
+            return Ok(true);
+        }
+
+        ensure_interactive(
+            "Confirming the rebuild",
+            "Pass --yes to confirm the rebuild up front.",
+        )?;
+
+        let help = if container.state == ContainerState::Running {
+            "The container is running and will be stopped, ending all of its sessions."
+        } else {
+            "All data/state outside the home directory will be lost."
+        };
+
+        Ok(
+            Confirm::new("Are you sure you want to replace the container of this Litterbox?")
+                .with_default(false)
+                .with_help_message(help)
+                .prompt()?,
+        )
+    }
 }
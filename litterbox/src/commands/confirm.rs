@@ -2,7 +2,16 @@ use anyhow::Result;
 use clap::Args;
 use eframe::egui;
 
-use crate::agent::{UserRequest, UserResponse};
+use crate::agent::{Severity, UserRequest, UserResponse};
+
+/// Banner colour used to tell dangerous requests apart at a glance
+fn severity_color(severity: Severity) -> Option<egui::Color32> {
+    match severity {
+        Severity::Low => None,
+        Severity::Medium => Some(egui::Color32::from_rgb(0xb3, 0x6b, 0x00)),
+        Severity::High => Some(egui::Color32::from_rgb(0xb0, 0x1c, 0x1c)),
+    }
+}
 
 struct ConfirmationDialog<'a> {
     user_response: &'a mut UserResponse,
@@ -25,23 +34,61 @@ impl eframe::App for ConfirmationDialog<'_> {
                 ui.label(egui::RichText::new(self.user_request.to_string()).strong());
             });
 
-            ui.horizontal(|ui| {
-                if ui.button("Approve").clicked() {
-                    *self.user_response = UserResponse::Approved;
-                    ui.ctx().send_viewport_cmd(egui::ViewportCommand::Close);
+            let description = egui::RichText::new(self.user_request.description());
+            match severity_color(self.user_request.severity()) {
+                Some(color) => {
+                    egui::Frame::new()
+                        .fill(color)
+                        .inner_margin(6.0)
+                        .corner_radius(4.0)
+                        .show(ui, |ui| {
+                            ui.set_width(ui.available_width());
+                            ui.label(description.color(egui::Color32::WHITE).strong());
+                        });
                 }
+                None => {
+                    ui.label(description);
+                }
+            }
+
+            let may_approve_for_session = *self.user_request == UserRequest::RequestKeys;
+
+            // Enter declines so that an absent-minded key press fails closed
+            let (approve_key, approve_for_session_key, decline_key, escape_key) = ui.input(|i| {
+                (
+                    i.key_pressed(egui::Key::Y),
+                    i.key_pressed(egui::Key::S),
+                    i.key_pressed(egui::Key::Enter),
+                    i.key_pressed(egui::Key::Escape),
+                )
+            });
 
-                if ui.button("Decline").clicked() {
-                    *self.user_response = UserResponse::Declined;
-                    ui.ctx().send_viewport_cmd(egui::ViewportCommand::Close);
+            let mut response = None;
+            ui.horizontal_wrapped(|ui| {
+                if ui.button("Approve (Y)").clicked() || approve_key {
+                    response = Some(UserResponse::Approved);
                 }
 
-                let may_approve_for_session = *self.user_request == UserRequest::RequestKeys;
-                if may_approve_for_session && ui.button("Approve for Session").clicked() {
-                    *self.user_response = UserResponse::ApprovedForSession;
-                    ui.ctx().send_viewport_cmd(egui::ViewportCommand::Close);
+                if ui.button("Decline (Enter/Esc)").clicked() {
+                    response = Some(UserResponse::Declined);
+                }
+
+                if may_approve_for_session
+                    && (ui.button("Approve for Session (S)").clicked() || approve_for_session_key)
+                {
+                    response = Some(UserResponse::ApprovedForSession);
                 }
             });
+
+            // Declining wins when keys for several actions are pressed at once
+            if decline_key || escape_key {
+                response = Some(UserResponse::Declined);
+            }
+
+            if let Some(response) = response {
+                *self.user_response = response;
+                ui.ctx().send_viewport_cmd(egui::ViewportCommand::Close);
+            }
         });
     }
 }
@@ -61,7 +108,7 @@ pub struct Command {
 impl Command {
     pub fn run(self) -> Result<()> {
         let mut native_options = eframe::NativeOptions::default();
-        native_options.viewport.inner_size = Some((270.0, 340.0).into());
+        native_options.viewport.inner_size = Some((320.0, 400.0).into());
 
         let user_request = self
             .request
@@ -1,18 +1,54 @@
 use anyhow::Result;
 use clap::Args;
+use inquire::Confirm;
+use std::io::IsTerminal;
 
-use crate::podman::define_litterbox;
+use crate::{
+    podman::{build_image, build_litterbox, define_litterbox, define_litterbox_from_stdin},
+    utils::assume_yes,
+};
 
 /// Define a new Litterbox using a template Dockerfile
 #[derive(Args, Debug)]
 pub struct Command {
     /// The name of the Litterbox to define
     name: String,
+
+    /// Read the Dockerfile from STDIN instead of using a template
+    #[arg(long, conflicts_with = "template")]
+    from_stdin: bool,
+
+    /// The template to use instead of choosing one interactively (see
+    /// `template list`)
+    #[arg(long)]
+    template: Option<String>,
 }
 
 impl Command {
     pub fn run(self) -> Result<()> {
-        define_litterbox(&self.name)?;
+        if self.from_stdin {
+            define_litterbox_from_stdin(&self.name)?;
+        } else {
+            define_litterbox(&self.name, self.template.as_deref())?;
+        }
+
+        let build_now = if assume_yes() {
+            true
+        } else if std::io::stdin().is_terminal() {
+            Confirm::new("Build this Litterbox now?")
+                .with_default(true)
+                .with_help_message("Choose no if you first want to adapt the Dockerfile.")
+                .prompt()?
+        } else {
+            false
+        };
+
+        if build_now {
+            let image_id = build_image(&self.name, &[], None, None, false)?;
+            build_litterbox(&self.name, &image_id, &[], &[], &[], None)?;
+        } else {
+            eprintln!("Run `litterbox build {}` when you are ready.", self.name);
+        }
 
         Ok(())
     }
@@ -1,18 +1,50 @@
 use anyhow::Result;
 use clap::Args;
 
-use crate::podman::delete_litterbox;
+use crate::podman::{
+    delete_all_litterboxes, delete_litterbox, pick_litterbox, print_deletion_plan,
+    print_deletion_plan_all,
+};
 
 /// Delete an existing Litterbox
 #[derive(Args, Debug)]
 pub struct Command {
-    /// The name of the Litterbox to delete
-    name: String,
+    /// The name of the Litterbox to delete (a glob pattern when used with
+    /// --all). You get to choose one when omitted.
+    name: Option<String>,
+
+    /// Delete all Litterboxes, or only those matching NAME (homes and definitions are kept)
+    #[arg(long)]
+    all: bool,
+
+    /// Keep the image so that the Litterbox can quickly be rebuilt from it
+    #[arg(long)]
+    keep_image: bool,
+
+    /// Only list the container, image and files that would be deleted
+    #[arg(long)]
+    dry_run: bool,
 }
 
 impl Command {
     pub fn run(self) -> Result<()> {
-        delete_litterbox(&self.name)?;
+        if self.dry_run {
+            return match self.name {
+                Some(pattern) if self.all => {
+                    print_deletion_plan_all(Some(&pattern), self.keep_image)
+                }
+                None if self.all => print_deletion_plan_all(None, self.keep_image),
+                Some(name) => print_deletion_plan(&name, self.keep_image),
+                None => print_deletion_plan(&pick_litterbox("delete")?, self.keep_image),
+            };
+        }
+
+        match self.name {
+            Some(pattern) if self.all => delete_all_litterboxes(Some(&pattern), self.keep_image)?,
+            None if self.all => delete_all_litterboxes(None, self.keep_image)?,
+            Some(name) => delete_litterbox(&name, self.keep_image)?,
+            None => delete_litterbox(&pick_litterbox("delete")?, self.keep_image)?,
+        }
 
         Ok(())
     }
@@ -1,23 +1,47 @@
 use anyhow::Result;
 use clap::Args;
 
-use crate::devices::attach_device;
+use crate::{
+    devices::{
+        DEFAULT_DEVICE_PERMS, attach_devices, default_device_owner, parse_device_path, parse_owner,
+        parse_perms, report_attached,
+    },
+    settings::AttachedDevice,
+};
 
-/// Attach a device to a Litterbox (the device fille be created in the home directory)
+/// Attach devices to a Litterbox (the device files will be created in the home directory)
 #[derive(Args, Debug)]
 pub struct Command {
     /// The name of the Litterbox to attach the device to
     name: String,
 
-    /// The path of the device to be attached
-    path: String,
+    /// The paths of the devices to be attached
+    #[arg(required = true, value_parser = parse_device_path)]
+    paths: Vec<String>,
+
+    /// Owner of the device node [default: your own UID:GID]
+    #[arg(long, value_name = "UID:GID", value_parser = parse_owner)]
+    owner: Option<String>,
+
+    /// Permissions of the device node as an octal mode
+    #[arg(long, default_value = DEFAULT_DEVICE_PERMS, value_parser = parse_perms)]
+    perms: String,
 }
 
 impl Command {
     pub fn run(self) -> Result<()> {
-        let dest_path = attach_device(&self.name, &self.path)?;
-        println!("Device attached at {:#?}!", dest_path);
+        let owner = self.owner.unwrap_or_else(default_device_owner);
+        let devices = self
+            .paths
+            .into_iter()
+            .map(|path| AttachedDevice {
+                path,
+                owner: owner.clone(),
+                perms: self.perms.clone(),
+                passthrough: false,
+            })
+            .collect();
 
-        Ok(())
+        report_attached(attach_devices(&self.name, devices)?)
     }
 }
@@ -1,20 +1,27 @@
 use crate::{
+    config::GlobalConfig,
     daemon, files,
     podman::{
-        get_container, is_container_running, start_daemon, wait_for_podman, wait_for_podman_async,
+        Container, ContainerState, build_image, build_litterbox, get_container, image_is_stale,
+        is_running, no_container_error, pick_litterbox, rebuild_litterbox, recreate_litterbox_with,
+        require_container, start_container, start_daemon,
     },
-    utils::trace_arguments,
+    settings::LitterboxSettings,
+    utils::{assume_yes, trace_arguments},
 };
-use anyhow::{Context as _, Result, anyhow};
+use anyhow::{Context as _, Result, anyhow, bail, ensure};
 use clap::Args;
+use inquire::Confirm;
 use log::{debug, info, warn};
-use nix::unistd::{Pid, getgid, getuid};
+use nix::unistd::Pid;
 use shared::entrypoint::CommonEntrypointOptions;
 use std::{
     fmt::Display,
+    io::IsTerminal,
     path::PathBuf,
     process::Stdio,
     str::{FromStr, ParseBoolError},
+    time::{Duration, Instant},
 };
 
 #[derive(Clone, Debug, Copy)]
@@ -34,6 +41,18 @@ impl FromStr for Tty {
     }
 }
 
+/// Options for the `podman exec` that runs a session
+#[derive(Debug, Default)]
+pub struct ExecOptions {
+    /// Working directory inside the container
+    pub workdir: Option<PathBuf>,
+    /// `KEY=VALUE` environment variables for the session
+    pub env: Vec<String>,
+    /// Key sequence for detaching from the session, falling back to the one
+    /// from the global config
+    pub detach_keys: Option<String>,
+}
+
 #[derive(Clone, Debug, Copy)]
 pub struct Interactive(pub bool);
 
@@ -54,8 +73,8 @@ impl FromStr for Interactive {
 /// Enter an existing Litterbox
 #[derive(Args, Debug)]
 pub struct Command {
-    /// The name of the Litterbox to enter
-    name: String,
+    /// The name of the Litterbox to enter. You get to choose one when omitted.
+    name: Option<String>,
 
     /// Make STDIN available to the contained process. Defaults to "true" if
     /// COMMAND is not supplied
@@ -70,67 +89,487 @@ pub struct Command {
     #[arg(long, short)]
     workdir: Option<PathBuf>,
 
+    /// Set an environment variable for this session only. Can be repeated.
+    #[arg(long = "env", short = 'e', value_name = "KEY=VALUE", value_parser = parse_env_var)]
+    env: Vec<String>,
+
+    /// Key sequence for detaching from the session instead of podman's
+    /// ctrl-p,ctrl-q (e.g. ctrl-a,d). An empty value disables detaching.
+    #[arg(long, value_name = "KEYS", value_parser = parse_detach_keys)]
+    detach_keys: Option<String>,
+
+    /// Number of CPUs for this session only. The container gets recreated for
+    /// it (and again afterwards), so it must not be running yet.
+    #[arg(long, value_parser = parse_cpus)]
+    cpus: Option<String>,
+
+    /// Memory limit for this session only (e.g. 8g). The container gets
+    /// recreated for it (and again afterwards), so it must not be running yet.
+    #[arg(long, value_parser = parse_memory)]
+    memory: Option<String>,
+
+    /// Limit of memory plus swap for this session (e.g. 12g), which must be at
+    /// least --memory. Setting it to the same value disables swap.
+    #[arg(long, value_parser = parse_memory, requires = "memory")]
+    memory_swap: Option<String>,
+
+    /// Build the Litterbox first if it does not exist yet
+    #[arg(long)]
+    auto_build: bool,
+
+    /// Do not warn when the Dockerfile was changed after the image was built
+    #[arg(long)]
+    no_stale_check: bool,
+
+    /// Open the session in a new terminal window ($TERMINAL or a detected one)
+    #[arg(long)]
+    new_window: bool,
+
     #[clap(flatten)]
     opts: CommonEntrypointOptions,
 }
 
 impl Command {
     pub fn run(self) -> Result<()> {
-        use std::process::Command;
+        if self.new_window {
+            return open_in_new_window();
+        }
 
-        let container = get_container(&self.name)?
-            .ok_or_else(|| anyhow!("No container found for '{}'", self.name))?;
-        let container_id = container.id;
+        let name = match self.name {
+            Some(name) => name,
+            None => pick_litterbox("enter")?,
+        };
 
-        if !daemon::is_running(&self.name)? {
-            if is_container_running(&self.name)? {
-                warn!("Daemon was not running but container was. Restarting daemon...");
+        let mut resource_args = Vec::new();
+        if let Some(cpus) = &self.cpus {
+            resource_args.push(format!("--cpus={cpus}"));
+        }
+        if let Some(memory) = &self.memory {
+            resource_args.push(format!("--memory={memory}"));
+        }
+        if let Some(memory_swap) = &self.memory_swap {
+            // Podman would only reject this once the container gets created
+            let memory = self.memory.as_deref().unwrap_or_default();
+            ensure!(
+                memory_bytes(memory_swap) >= memory_bytes(memory),
+                "--memory-swap ({memory_swap}) should be at least --memory ({memory})"
+            );
+            resource_args.push(format!("--memory-swap={memory_swap}"));
+        }
+
+        let mut container = match get_container(&name)? {
+            Some(container) => container,
+            None if self.auto_build => {
+                info!("No container found for '{}'; building it first...", name);
+
+                // This also falls through to `define` if there is no Dockerfile yet.
+                let image_id = build_image(&name, &[], None, None, false)?;
+                build_litterbox(&name, &image_id, &[], &[], &[], None)?;
+
+                require_container(&name)?
             }
+            None => bail!(
+                "{}\nUse --auto-build to build it first.",
+                no_container_error(&name)
+            ),
+        };
+
+        if !self.no_stale_check && image_is_stale(&name, &container)? {
+            container = offer_rebuild(&name, container)?;
+        }
 
-            start_daemon(&self.name)?;
+        let mut temporary_resources = None;
+        if !resource_args.is_empty() {
+            if container.state == ContainerState::Running {
+                bail!(
+                    "Litterbox '{name}' is already running, so its resources cannot be changed. \
+                     Exit all of its sessions first."
+                );
+            }
+
+            eprintln!("Recreating the container with temporary resource limits...");
+            recreate_litterbox_with(&name, &resource_args)?;
+            temporary_resources = Some(TemporaryResources { name: name.clone() });
+            container = require_container(&name)?;
         }
 
-        let my_pid = Pid::this();
-        let session_lock = files::session_lock_path(&self.name)?;
-        files::append_pid_to_session_lockfile(&session_lock, my_pid)?;
+        let mut opts = self.opts;
+        let mut interactive = self.interactive;
+        let mut tty = self.tty;
+
+        // A configured start command or multiplexer replaces the login shell,
+        // so it gets the same terminal defaults.
+        let start_command = match opts.command {
+            Some(_) => None,
+            None => LitterboxSettings::load(&name)?.and_then(|s| {
+                s.start_command.or_else(|| {
+                    let command = s.multiplexer.command()?;
+                    Some(command.iter().map(|arg| arg.to_string()).collect())
+                })
+            }),
+        };
+        if let Some(start_command) = &start_command {
+            let (command, args) = start_command
+                .split_first()
+                .ok_or_else(|| anyhow!("The start command for '{}' is empty", name))?;
+
+            debug!("Using start command: {start_command:?}");
+            opts.command = Some(command.into());
+            opts.args = args.iter().map(Into::into).collect();
+            interactive = Interactive(true);
+            tty = Tty(true);
+        }
 
-        if !is_container_running(&self.name)? {
-            info!("Container is not running yet; starting now...");
+        let exec = ExecOptions {
+            workdir: self.workdir,
+            env: self.env,
+            detach_keys: self.detach_keys,
+        };
+        let session_end = enter_container(&name, container, interactive, tty, exec, opts)?;
+        // Restored before exiting below, which would skip the drop
+        drop(temporary_resources);
+
+        if let Some(start_command) = start_command
+            && let SessionEnd::Exited { code } = session_end
+            && code != Some(0)
+        {
+            let start_command = start_command.join(" ");
+            match code {
+                // What shells (and podman exec) exit with for unknown commands
+                Some(127) => bail!(
+                    "The start command {start_command:?} was not found. Make sure that it exists \
+                     inside the Litterbox or change it in {:?}.",
+                    files::settings_path(&name)?,
+                ),
+                Some(code) => bail!("The start command {start_command:?} exited with code {code}."),
+                None => bail!("The start command {start_command:?} was killed by a signal."),
+            }
+        }
 
-            let mut cmd = Command::new("podman");
-            cmd.stdout(Stdio::null());
-            cmd.args(["start", &container_id]);
-            trace_arguments(&cmd);
+        // Like shells do, report the interrupt as 128 + SIGINT
+        if session_end == SessionEnd::Interrupted {
+            std::process::exit(130);
+        }
 
-            let start_child = cmd.spawn().context("Failed to run podman command")?;
-            wait_for_podman(start_child)?;
-        } else {
-            debug!("Container {container_id:?} is already running; just attaching...")
+        Ok(())
+    }
+}
+
+/// Restores the resource limits of the container when dropped, regardless of
+/// how the session ended.
+struct TemporaryResources {
+    name: String,
+}
+
+impl Drop for TemporaryResources {
+    fn drop(&mut self) {
+        if let Err(e) = restore_resources(&self.name) {
+            warn!(
+                "Failed to restore the resource limits of '{}': {e:#}",
+                self.name
+            );
         }
+    }
+}
 
-        tokio::runtime::Runtime::new()
-            .expect("Tokio runtime should start")
-            .block_on(container_exec_entrypoint(
-                container_id,
-                self.interactive,
-                self.tty,
-                self.workdir,
-                self.opts,
-            ))?;
+/// Recreates the container without the temporary resource limits once it has
+/// stopped, which interactive Litterboxes do after their last session.
+fn restore_resources(lbx_name: &str) -> Result<()> {
+    let interactive = LitterboxSettings::load(lbx_name)?.is_none_or(|s| s.interactive);
+    let deadline = Instant::now() + crate::env::podman_timeout();
+
+    while is_running(lbx_name)? {
+        if !interactive || Instant::now() > deadline {
+            warn!(
+                "Litterbox '{lbx_name}' is still running with the temporary resource limits. \
+                 Run `litterbox recreate {lbx_name}` once it has stopped to restore them."
+            );
+            return Ok(());
+        }
 
-        files::remove_pid_from_session_lockfile(&session_lock, my_pid)?;
+        std::thread::sleep(Duration::from_millis(500));
+    }
 
-        Ok(())
+    recreate_litterbox_with(lbx_name, &[])?;
+    eprintln!("Restored the resource limits of '{lbx_name}'.");
+    Ok(())
+}
+
+/// Validates a CPU count for `--cpus`, which may be fractional
+fn parse_cpus(cpus: &str) -> Result<String> {
+    ensure!(
+        cpus.parse::<f64>().is_ok_and(|n| n.is_finite() && n > 0.0),
+        "CPUs \"{cpus}\" should be a positive number like 2 or 1.5"
+    );
+
+    Ok(cpus.to_owned())
+}
+
+/// Validates a memory limit for `--memory`, i.e. a number with an optional
+/// b, k, m or g suffix
+fn parse_memory(memory: &str) -> Result<String> {
+    ensure!(
+        memory_bytes(memory).is_some_and(|n| n > 0),
+        "Memory \"{memory}\" should be a positive number with an optional unit like 512m or 8g"
+    );
+
+    Ok(memory.to_owned())
+}
+
+/// The number of bytes a memory limit as accepted by podman stands for
+fn memory_bytes(memory: &str) -> Option<u64> {
+    let number = memory.trim_end_matches(['b', 'k', 'm', 'g', 'B', 'K', 'M', 'G']);
+    let multiplier = match &memory[number.len()..] {
+        "" | "b" | "B" => 1,
+        "k" | "K" => 1 << 10,
+        "m" | "M" => 1 << 20,
+        "g" | "G" => 1 << 30,
+        _ => return None,
+    };
+
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// Parses a `KEY=VALUE` environment variable given on the command line.
+fn parse_env_var(var: &str) -> Result<String> {
+    let (key, _value) = var
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Environment variable \"{var}\" should be in the form KEY=VALUE"))?;
+
+    ensure!(
+        !key.is_empty(),
+        "Environment variable \"{var}\" has an empty key"
+    );
+    ensure!(
+        !key.chars().any(char::is_whitespace),
+        "Environment variable \"{var}\" has whitespace in its key"
+    );
+
+    Ok(var.to_owned())
+}
+
+/// Parses a podman detach key sequence: comma separated keys that are either
+/// a single character or `ctrl-` followed by one of a-z, @, [, \, ], ^ or _.
+fn parse_detach_keys(keys: &str) -> Result<String> {
+    if keys.is_empty() {
+        return Ok(String::new());
     }
+
+    for key in keys.split(',') {
+        let valid = match key.strip_prefix("ctrl-") {
+            Some(ctrl) => {
+                let mut chars = ctrl.chars();
+                matches!(
+                    (chars.next(), chars.next()),
+                    (Some('a'..='z' | '@' | '[' | '\\' | ']' | '^' | '_'), None)
+                )
+            }
+            None => key.chars().count() == 1,
+        };
+
+        ensure!(
+            valid,
+            "Invalid detach key \"{key}\" in \"{keys}\", expected a single character or \
+             ctrl-<value> (e.g. ctrl-a,d)"
+        );
+    }
+
+    Ok(keys.to_owned())
+}
+
+/// Terminal emulators to look for when `$TERMINAL` is not set, along with the
+/// arguments that make them run a command.
+const TERMINALS: &[(&str, &[&str])] = &[
+    ("x-terminal-emulator", &["-e"]),
+    ("foot", &[]),
+    ("kitty", &[]),
+    ("alacritty", &["-e"]),
+    ("wezterm", &["start", "--"]),
+    ("ghostty", &["-e"]),
+    ("gnome-terminal", &["--"]),
+    ("konsole", &["-e"]),
+    ("xterm", &["-e"]),
+];
+
+fn find_terminal() -> Option<(String, Vec<&'static str>)> {
+    // Most terminals accept `-e`, so that is assumed for `$TERMINAL`
+    if let Ok(terminal) = std::env::var("TERMINAL")
+        && !terminal.trim().is_empty()
+    {
+        return Some((terminal, vec!["-e"]));
+    }
+
+    let path = std::env::var_os("PATH")?;
+    TERMINALS.iter().find_map(|(binary, args)| {
+        std::env::split_paths(&path)
+            .any(|dir| dir.join(binary).is_file())
+            .then(|| (binary.to_string(), args.to_vec()))
+    })
+}
+
+/// Re-runs the current `enter` command inside a new terminal window and
+/// returns without waiting for it.
+fn open_in_new_window() -> Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    let (terminal, terminal_args) = find_terminal().ok_or_else(|| {
+        anyhow!("No terminal emulator found. Set $TERMINAL to the one you would like to use.")
+    })?;
+
+    let mut cmd = std::process::Command::new(&terminal);
+    cmd.args(terminal_args);
+    cmd.arg(crate::env::litterbox_binary_path());
+    cmd.args(
+        std::env::args_os()
+            .skip(1)
+            .filter(|arg| arg != "--new-window"),
+    );
+
+    // Detach so that closing the current shell does not close the window
+    cmd.stdin(Stdio::null());
+    cmd.stdout(Stdio::null());
+    cmd.stderr(Stdio::null());
+    cmd.process_group(0);
+
+    let _span = trace_arguments(&cmd);
+    cmd.spawn()
+        .with_context(|| format!("Failed to launch terminal emulator '{terminal}'"))?;
+
+    Ok(())
+}
+
+/// Warns that the image of a Litterbox is older than its Dockerfile and offers
+/// to rebuild it, unless the container is running and would have to be stopped.
+/// Returns the container to enter.
+fn offer_rebuild(lbx_name: &str, container: Container) -> Result<Container> {
+    warn!(
+        "The Dockerfile of '{lbx_name}' was changed after its image was built. \
+         Run `litterbox build --force {lbx_name}` to apply the changes."
+    );
+
+    if container.state == ContainerState::Running || assume_yes() || !std::io::stdin().is_terminal()
+    {
+        return Ok(container);
+    }
+
+    let rebuild = Confirm::new("Would you like to rebuild it before entering?")
+        .with_default(false)
+        .with_help_message("All data/state outside the home directory will be lost.")
+        .prompt()?;
+    if !rebuild {
+        return Ok(container);
+    }
+
+    rebuild_litterbox(&container, None)?;
+    require_container(lbx_name)
+}
+
+/// How a session inside a Litterbox ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionEnd {
+    /// The entrypoint exited by itself with the given exit code, which is
+    /// missing if it was killed by a signal
+    Exited { code: Option<i32> },
+    /// The session was cut short with Ctrl-C
+    Interrupted,
+}
+
+/// Starts the container (and its daemon) if needed and then runs the
+/// entrypoint inside it for the duration of the session. Returns how the
+/// session ended.
+pub fn enter_container(
+    lbx_name: &str,
+    container: Container,
+    interactive: Interactive,
+    tty: Tty,
+    mut exec: ExecOptions,
+    opts: CommonEntrypointOptions,
+) -> Result<SessionEnd> {
+    // The container details were only just queried, so we avoid asking podman
+    // about its state again.
+    let container_id = container.id;
+    let container_running = container.state == ContainerState::Running;
+
+    // The agent socket is shared by all sessions, so it can only be kept out
+    // while no other session has started the agent. Likewise, the agent must
+    // not be started while a session without it is still around.
+    let my_pid = Pid::this();
+    let no_agent = opts.no_agent;
+    let no_agent_lock = files::no_agent_lock_path(lbx_name)?;
+    if no_agent {
+        ensure!(
+            !daemon::is_running(lbx_name)?,
+            "The SSH agent of '{lbx_name}' is already running for other sessions. \
+             Leave them before entering with --no-agent."
+        );
+        debug!("Not starting the daemon since --no-agent was passed");
+        files::append_pid_to_session_lockfile(&no_agent_lock, my_pid)?;
+    } else if !daemon::is_running(lbx_name)? {
+        files::cleanup_dead_pids_from_session_lockfile(&no_agent_lock)?;
+        ensure!(
+            files::read_pids_from_session_lockfile(&no_agent_lock)?.is_empty(),
+            "'{lbx_name}' has a session that was entered with --no-agent, which could reach \
+             the SSH agent if it got started now. Leave it first, or pass --no-agent as well."
+        );
+
+        if container_running {
+            warn!("Daemon was not running but container was. Restarting daemon...");
+        }
+
+        start_daemon(lbx_name)?;
+    }
+
+    let session_lock = files::session_lock_path(lbx_name)?;
+    files::append_pid_to_session_lockfile(&session_lock, my_pid)?;
+
+    if !container_running {
+        info!("Container is not running yet; starting now...");
+        start_container(lbx_name, &container_id)?;
+    } else {
+        debug!("Container {container_id:?} is already running; just attaching...")
+    }
+
+    let user = match LitterboxSettings::load(lbx_name)? {
+        Some(settings) => settings.container_user(),
+        None => crate::env::host_user_ids(),
+    };
+
+    if exec.detach_keys.is_none()
+        && let Some(keys) = GlobalConfig::load()?.detach_keys
+    {
+        exec.detach_keys =
+            Some(parse_detach_keys(&keys).context("Invalid detach_keys in the global config")?);
+    }
+
+    let session_end = tokio::runtime::Runtime::new()
+        .expect("Tokio runtime should start")
+        .block_on(container_exec_entrypoint(
+            container_id,
+            user,
+            interactive,
+            tty,
+            exec,
+            opts,
+        ));
+
+    // However the session ended, it must no longer keep the container (and
+    // with it the daemon and its SSH agent) alive.
+    files::remove_pid_from_session_lockfile(&session_lock, my_pid)?;
+    if no_agent {
+        files::remove_pid_from_session_lockfile(&no_agent_lock, my_pid)?;
+    }
+
+    session_end
 }
 
 async fn container_exec_entrypoint(
     container_id: String,
+    (uid, gid): (u32, u32),
     interactive: Interactive,
     tty: Tty,
-    workdir: Option<PathBuf>,
+    exec: ExecOptions,
     opts: CommonEntrypointOptions,
-) -> Result<()> {
+) -> Result<SessionEnd> {
     use tokio::process::Command;
 
     let mut exec_child = Command::new("podman");
@@ -147,11 +586,20 @@ async fn container_exec_entrypoint(
         exec_child.arg("--interactive");
     }
 
-    if let Some(workdir) = workdir {
+    if let Some(workdir) = exec.workdir {
         exec_child.arg("--workdir");
         exec_child.arg(workdir.into_os_string());
     }
 
+    for var in exec.env {
+        exec_child.arg("--env");
+        exec_child.arg(var);
+    }
+
+    if let Some(detach_keys) = exec.detach_keys {
+        exec_child.arg(format!("--detach-keys={detach_keys}"));
+    }
+
     // We always start as root but drop permissions later if needed
     exec_child.arg("--user");
     exec_child.arg("root");
@@ -161,9 +609,9 @@ async fn container_exec_entrypoint(
         "/lbx-init",
         "entrypoint",
         "--uid",
-        &getuid().to_string(),
+        &uid.to_string(),
         "--gid",
-        &getgid().to_string(),
+        &gid.to_string(),
         "--wait",
         &opts.wait.to_string(),
     ]);
@@ -173,6 +621,10 @@ async fn container_exec_entrypoint(
         exec_child.arg("--root");
     }
 
+    if opts.no_agent {
+        exec_child.arg("--no-agent");
+    }
+
     if let Some(command) = opts.command {
         exec_child.arg("--");
         exec_child.arg(command);
@@ -182,14 +634,68 @@ async fn container_exec_entrypoint(
     let mut exec_child = exec_child.spawn().context("Failed to run podman command")?;
     debug!("Entering Litterbox...");
 
-    tokio::select! {
-        _ = wait_for_podman_async(&mut exec_child) => {}
+    let session_end = tokio::select! {
+        res = exec_child.wait() => SessionEnd::Exited {
+            code: res.context("Failed to run podman command")?.code(),
+        },
         _ = tokio::signal::ctrl_c() => {
-            let _ = exec_child.kill().await;
+            eprintln!("Interrupted, leaving the Litterbox...");
+            if let Err(e) = exec_child.kill().await {
+                warn!("Failed to stop the session: {e}");
+            }
+            SessionEnd::Interrupted
         }
-    }
+    };
 
     debug!("Exited Litterbox");
 
-    Ok(())
+    Ok(session_end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_env_vars() {
+        assert_eq!(parse_env_var("FOO=bar").unwrap(), "FOO=bar");
+        assert_eq!(parse_env_var("FOO=").unwrap(), "FOO=");
+        assert_eq!(parse_env_var("FOO=a=b").unwrap(), "FOO=a=b");
+        assert!(parse_env_var("FOO").is_err());
+        assert!(parse_env_var("=bar").is_err());
+        assert!(parse_env_var("MY VAR=bar").is_err());
+    }
+
+    #[test]
+    fn validates_detach_keys() {
+        assert_eq!(parse_detach_keys("ctrl-a,d").unwrap(), "ctrl-a,d");
+        assert_eq!(parse_detach_keys("ctrl-@,ctrl-_").unwrap(), "ctrl-@,ctrl-_");
+        assert_eq!(parse_detach_keys("").unwrap(), "");
+        assert!(parse_detach_keys("ctrl-A").is_err());
+        assert!(parse_detach_keys("ctrl-ab").is_err());
+        assert!(parse_detach_keys("ctrl-a,,d").is_err());
+        assert!(parse_detach_keys("alt-x").is_err());
+    }
+
+    #[test]
+    fn validates_resource_limits() {
+        assert!(parse_cpus("2").is_ok());
+        assert!(parse_cpus("1.5").is_ok());
+        assert!(parse_cpus("0").is_err());
+        assert!(parse_cpus("-1").is_err());
+        assert!(parse_cpus("many").is_err());
+
+        assert!(parse_memory("8g").is_ok());
+        assert!(parse_memory("512M").is_ok());
+        assert!(parse_memory("1073741824").is_ok());
+        assert!(parse_memory("0g").is_err());
+        assert!(parse_memory("8gb").is_err());
+        assert!(parse_memory("g").is_err());
+        assert!(parse_memory("1.5g").is_err());
+
+        assert_eq!(memory_bytes("512m"), Some(512 << 20));
+        assert_eq!(memory_bytes("2G"), Some(2 << 30));
+        assert!(memory_bytes("1g") > memory_bytes("1000m"));
+        assert_eq!(memory_bytes("99999999999999g"), None);
+    }
 }
@@ -0,0 +1,25 @@
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+
+use crate::definition::export_definition;
+
+/// Export the Dockerfile and settings of a Litterbox for sharing, without any
+/// keys or host-specific settings
+#[derive(Args, Debug)]
+pub struct Command {
+    /// The name of the Litterbox to export
+    name: String,
+
+    /// Where to write the bundle
+    path: PathBuf,
+}
+
+impl Command {
+    pub fn run(self) -> Result<()> {
+        export_definition(&self.name, &self.path)?;
+        eprintln!("Definition of '{}' exported to {:?}", self.name, self.path);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,24 @@
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+
+use crate::definition::import_definition;
+
+/// Define a new Litterbox from a bundle made with `export-def`
+#[derive(Args, Debug)]
+pub struct Command {
+    /// The bundle to import
+    path: PathBuf,
+
+    /// The name of the Litterbox to define
+    name: String,
+}
+
+impl Command {
+    pub fn run(self) -> Result<()> {
+        import_definition(&self.path, &self.name)?;
+        eprintln!("Run `litterbox build {}` when you are ready.", self.name);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,24 @@
+use anyhow::Result;
+use clap::Args;
+
+use crate::podman::inspect_container;
+
+/// Print the raw `podman inspect` output of a Litterbox's container
+#[derive(Args, Debug)]
+pub struct Command {
+    /// The name of the Litterbox
+    name: String,
+
+    /// Go template to format the output with, passed on to podman
+    #[arg(long)]
+    format: Option<String>,
+}
+
+impl Command {
+    pub fn run(self) -> Result<()> {
+        let output = inspect_container(&self.name, self.format.as_deref())?;
+        print!("{output}");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,18 @@
+use crate::keys::Keys;
+use anyhow::Result;
+use clap::Args;
+
+/// Store a named password, e.g. for use with `build --password-from`
+#[derive(Args, Debug)]
+pub struct Command {
+    /// The name of the secret
+    name: String,
+}
+
+impl Command {
+    pub fn run(self, mut keys: Keys) -> Result<()> {
+        keys.add_secret(&self.name)?;
+
+        Ok(())
+    }
+}
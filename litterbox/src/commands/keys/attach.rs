@@ -1,20 +1,36 @@
-use crate::keys::Keys;
+use crate::{keys::Keys, podman::get_containers};
 use anyhow::Result;
 use clap::Args;
 
-/// Attach an existing key to a Litterbox
+/// Attach an existing key to one or more Litterboxes
 #[derive(Args, Debug)]
 pub struct Command {
     /// The name of the key
     key_name: String,
 
-    /// The name of the Litterbox
-    litterbox_name: String,
+    /// The names of the Litterboxes (chosen interactively when left out)
+    #[arg(conflicts_with = "all_boxes")]
+    litterbox_names: Vec<String>,
+
+    /// Attach the key to every existing Litterbox it is not attached to yet
+    #[arg(long)]
+    all_boxes: bool,
 }
 
 impl Command {
     pub fn run(self, mut keys: Keys) -> Result<()> {
-        keys.attach(&self.key_name, &self.litterbox_name)?;
+        if self.litterbox_names.is_empty() {
+            let names = get_containers(&[])?.names();
+
+            if self.all_boxes {
+                return keys.attach_all(&self.key_name, names);
+            }
+            return keys.attach_interactive(&self.key_name, names);
+        }
+
+        for litterbox_name in &self.litterbox_names {
+            keys.attach(&self.key_name, litterbox_name)?;
+        }
 
         Ok(())
     }
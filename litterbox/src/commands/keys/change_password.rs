@@ -1,14 +1,38 @@
-use crate::keys::Keys;
+use crate::keys::{KdfParams, Keys};
 use anyhow::Result;
 use clap::Args;
 
 /// Change the password used to encrypt passwords for storage
 #[derive(Args, Debug)]
-pub struct Command {}
+pub struct Command {
+    /// Argon2 memory cost in KiB to use from now on
+    #[arg(long)]
+    memory_kib: Option<u32>,
+
+    /// Argon2 number of passes to use from now on
+    #[arg(long)]
+    iterations: Option<u32>,
+
+    /// Argon2 degree of parallelism to use from now on
+    #[arg(long)]
+    parallelism: Option<u32>,
+
+    /// PBKDF2 rounds for encrypting keys to use from now on
+    #[arg(long)]
+    pbkdf2_rounds: Option<u32>,
+}
 
 impl Command {
     pub fn run(self, mut keys: Keys) -> Result<()> {
-        keys.change_password()?;
+        let current = keys.kdf();
+        let kdf = KdfParams {
+            memory_kib: self.memory_kib.unwrap_or(current.memory_kib),
+            iterations: self.iterations.unwrap_or(current.iterations),
+            parallelism: self.parallelism.unwrap_or(current.parallelism),
+            pbkdf2_rounds: self.pbkdf2_rounds.unwrap_or(current.pbkdf2_rounds),
+        };
+
+        keys.change_password(kdf)?;
 
         Ok(())
     }
@@ -14,7 +14,7 @@ pub struct Command {
 
 impl Command {
     pub fn run(self, mut keys: Keys) -> Result<()> {
-        keys.import_key(&self.name, self.path)?;
+        keys.import_key(&self.name, self.path, None)?;
 
         Ok(())
     }
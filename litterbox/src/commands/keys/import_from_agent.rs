@@ -0,0 +1,75 @@
+use anyhow::{Result, bail, ensure};
+use clap::Args;
+use inquire::Select;
+use russh::keys::{HashAlg, agent::AgentIdentity};
+use std::path::PathBuf;
+
+use crate::{
+    agent::host_agent_identities,
+    files::host_ssh_dir_path,
+    keys::{Keys, find_private_key_file},
+    utils::ensure_interactive,
+};
+
+/// Import a key that is loaded in the SSH agent of the host
+#[derive(Args, Debug)]
+pub struct Command {
+    /// The name of the new key
+    name: String,
+
+    /// The private key file of the chosen key, if it is not next to its
+    /// `.pub` file in ~/.ssh
+    #[arg(long)]
+    path: Option<PathBuf>,
+}
+
+fn describe(identity: &AgentIdentity) -> String {
+    let fingerprint = identity.public_key().fingerprint(HashAlg::Sha256);
+    match identity.comment() {
+        "" => fingerprint.to_string(),
+        comment => format!("{comment} ({fingerprint})"),
+    }
+}
+
+impl Command {
+    pub fn run(self, mut keys: Keys) -> Result<()> {
+        let mut identities = tokio::runtime::Runtime::new()?.block_on(host_agent_identities())?;
+        ensure!(!identities.is_empty(), "The SSH agent has no keys loaded.");
+
+        let identity = if identities.len() == 1 {
+            identities.swap_remove(0)
+        } else {
+            ensure_interactive(
+                "Choosing a key from the SSH agent",
+                "Import the key from its file with `litterbox keys import` instead.",
+            )?;
+
+            let options: Vec<String> = identities.iter().map(describe).collect();
+            let choice = Select::new("Which key would you like to import?", options)
+                .raw_prompt()?
+                .index;
+            identities.swap_remove(choice)
+        };
+        let description = describe(&identity);
+        let public_key = identity.public_key();
+
+        // The agent protocol only lets clients use keys, not read them
+        eprintln!(
+            "SSH agents do not hand out private keys, so it will be read from its file instead."
+        );
+        let path = match self
+            .path
+            .or_else(|| find_private_key_file(&host_ssh_dir_path().ok()?, &public_key))
+        {
+            Some(path) => path,
+            None => bail!(
+                "Could not find the private key file of {description} in ~/.ssh.\n\
+                 Pass it with --path, or import it with `litterbox keys import {} PATH`.",
+                self.name
+            ),
+        };
+        eprintln!("Importing {description} from {path:?}.");
+
+        keys.import_key(&self.name, path, Some(&public_key))
+    }
+}
@@ -4,11 +4,18 @@ use clap::Args;
 
 /// List all the keys that are being managed
 #[derive(Args, Debug)]
-pub struct Command {}
+pub struct Command {
+    /// Only list the keys that are not attached to any Litterbox
+    #[arg(long)]
+    unused: bool,
+}
 
 impl Command {
-    pub fn run(self, keys: Keys) -> Result<()> {
-        keys.print_list();
+    pub fn run(self, keys: Option<Keys>) -> Result<()> {
+        match keys {
+            Some(keys) => keys.print_list(self.unused),
+            None => eprintln!("No keys have been created yet."),
+        }
 
         Ok(())
     }
@@ -2,25 +2,35 @@ use crate::keys::Keys;
 use anyhow::Result;
 use clap::Subcommand;
 
+mod add_secret;
 mod attach;
 mod change_password;
 mod delete;
+mod delete_secret;
 mod detach;
 mod export;
 mod generate;
 mod import;
+mod import_from_agent;
 mod list;
 mod print;
+mod set_default;
+mod unset_default;
+mod verify;
 
 /// Manage SSH keys that can be exposed to Litterboxes
 #[derive(Subcommand, Debug)]
 pub enum Command {
+    AddSecret(#[clap(flatten)] add_secret::Command),
+
     Attach(#[clap(flatten)] attach::Command),
 
     ChangePassword(#[clap(flatten)] change_password::Command),
 
     Delete(#[clap(flatten)] delete::Command),
 
+    DeleteSecret(#[clap(flatten)] delete_secret::Command),
+
     Detach(#[clap(flatten)] detach::Command),
 
     Export(#[clap(flatten)] export::Command),
@@ -29,26 +39,40 @@ pub enum Command {
 
     Import(#[clap(flatten)] import::Command),
 
+    ImportFromAgent(#[clap(flatten)] import_from_agent::Command),
+
     #[clap(visible_alias("ls"))]
     List(#[clap(flatten)] list::Command),
 
     Print(#[clap(flatten)] print::Command),
+
+    SetDefault(#[clap(flatten)] set_default::Command),
+
+    UnsetDefault(#[clap(flatten)] unset_default::Command),
+
+    Verify(#[clap(flatten)] verify::Command),
 }
 
 impl Command {
     pub fn run(self) -> Result<()> {
-        let keys = Keys::load()?;
-
+        // Read-only commands should not force the user to choose a password
+        // when no keys file exists yet.
         match self {
-            Command::List(command) => command.run(keys),
-            Command::Generate(command) => command.run(keys),
-            Command::Import(command) => command.run(keys),
-            Command::Delete(command) => command.run(keys),
-            Command::Attach(command) => command.run(keys),
-            Command::Detach(command) => command.run(keys),
-            Command::Export(command) => command.run(keys),
-            Command::Print(command) => command.run(keys),
-            Command::ChangePassword(command) => command.run(keys),
+            Command::List(command) => command.run(Keys::load_if_exists()?),
+            Command::Print(command) => command.run(Keys::load_if_exists()?),
+            Command::Verify(command) => command.run(Keys::load_if_exists()?),
+            Command::Generate(command) => command.run(Keys::load()?),
+            Command::Import(command) => command.run(Keys::load()?),
+            Command::ImportFromAgent(command) => command.run(Keys::load()?),
+            Command::Delete(command) => command.run(Keys::load()?),
+            Command::AddSecret(command) => command.run(Keys::load()?),
+            Command::DeleteSecret(command) => command.run(Keys::load()?),
+            Command::Attach(command) => command.run(Keys::load()?),
+            Command::Detach(command) => command.run(Keys::load()?),
+            Command::SetDefault(command) => command.run(Keys::load()?),
+            Command::UnsetDefault(command) => command.run(Keys::load()?),
+            Command::Export(command) => command.run(Keys::load()?),
+            Command::ChangePassword(command) => command.run(Keys::load()?),
         }
     }
 }
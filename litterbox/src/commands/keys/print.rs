@@ -1,5 +1,5 @@
 use crate::keys::Keys;
-use anyhow::Result;
+use anyhow::{Result, bail};
 use clap::Args;
 
 /// Print the key in OpenSSH public key format
@@ -14,7 +14,11 @@ pub struct Command {
 }
 
 impl Command {
-    pub fn run(self, keys: Keys) -> Result<()> {
+    pub fn run(self, keys: Option<Keys>) -> Result<()> {
+        let Some(keys) = keys else {
+            bail!("Key \"{}\" does not exist", self.key_name);
+        };
+
         keys.print(&self.key_name, self.private)?;
 
         Ok(())
@@ -0,0 +1,18 @@
+use crate::keys::Keys;
+use anyhow::Result;
+use clap::Args;
+
+/// Attach a key to every newly built Litterbox
+#[derive(Args, Debug)]
+pub struct Command {
+    /// The name of the key
+    key_name: String,
+}
+
+impl Command {
+    pub fn run(self, mut keys: Keys) -> Result<()> {
+        keys.set_default(&self.key_name, true)?;
+
+        Ok(())
+    }
+}
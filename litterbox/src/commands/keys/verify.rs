@@ -0,0 +1,22 @@
+use crate::keys::Keys;
+use anyhow::{Result, bail};
+use clap::Args;
+
+/// Check that a key can be decrypted and print its fingerprint
+#[derive(Args, Debug)]
+pub struct Command {
+    /// The name of the key
+    key_name: String,
+}
+
+impl Command {
+    pub fn run(self, keys: Option<Keys>) -> Result<()> {
+        let Some(keys) = keys else {
+            bail!("Key \"{}\" does not exist", self.key_name);
+        };
+
+        keys.verify(&self.key_name)?;
+
+        Ok(())
+    }
+}
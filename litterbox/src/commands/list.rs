@@ -1,8 +1,17 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use clap::Args;
-use tabled::{Table, Tabled};
+use std::collections::HashMap;
+use tabled::{
+    Table, Tabled,
+    settings::{Remove, location::ByColumnName},
+};
 
-use crate::podman::{Container, get_containers};
+use crate::{
+    files::{dir_size, lbx_home_path},
+    output::{self, LitterboxEntry},
+    podman::{Container, get_containers, get_image_sizes},
+    utils::format_size,
+};
 
 #[derive(Tabled)]
 struct ContainerTableRow {
@@ -11,6 +20,7 @@ struct ContainerTableRow {
     container_names: String,
     image: String,
     image_id: String,
+    size: String,
 }
 
 impl From<&Container> for ContainerTableRow {
@@ -21,19 +31,93 @@ impl From<&Container> for ContainerTableRow {
             container_names: value.names.join(","),
             image: value.image.clone(),
             image_id: value.image_id.chars().take(12).collect(),
+            size: String::new(),
         }
     }
 }
 
+fn parse_label_filter(filter: &str) -> Result<String> {
+    let label = filter
+        .strip_prefix("label=")
+        .filter(|label| !label.is_empty())
+        .ok_or_else(|| anyhow!("Filter \"{filter}\" should be in the form label=KEY[=VALUE]"))?;
+
+    Ok(format!("label={label}"))
+}
+
 /// List all the Litterboxes that have been created
 #[derive(Args, Debug)]
-pub struct Command {}
+pub struct Command {
+    /// Only list Litterboxes with a matching label (can be repeated)
+    #[arg(long = "filter", value_name = "label=KEY[=VALUE]", value_parser = parse_label_filter)]
+    filters: Vec<String>,
+
+    /// Show the disk space used by the image and home directory of each
+    /// Litterbox (slow for large home directories)
+    #[arg(long)]
+    size: bool,
+
+    /// Print the Litterboxes as versioned JSON for scripts
+    #[arg(long)]
+    json: bool,
+}
 
 impl Command {
     pub fn run(self) -> Result<()> {
-        let containers = get_containers()?;
-        let table_rows: Vec<ContainerTableRow> = containers.0.iter().map(|c| c.into()).collect();
-        let table = Table::new(table_rows);
+        let containers = get_containers(&self.filters)?;
+        let image_sizes = if self.size {
+            get_image_sizes()?
+        } else {
+            HashMap::new()
+        };
+
+        let mut sizes = Vec::new();
+        for container in &containers.0 {
+            let size = if self.size {
+                let image_size = image_sizes.get(&container.image_id).copied();
+                let home_size = dir_size(&lbx_home_path(&container.labels.name)?);
+                Some(image_size.unwrap_or_default() + home_size)
+            } else {
+                None
+            };
+            sizes.push(size);
+        }
+
+        if self.json {
+            let entries: Vec<LitterboxEntry> = containers
+                .0
+                .iter()
+                .zip(sizes)
+                .map(|(container, size_bytes)| LitterboxEntry {
+                    name: container.labels.name.clone(),
+                    container_id: container.id.clone(),
+                    container_names: container.names.clone(),
+                    image: container.image.clone(),
+                    image_id: container.image_id.clone(),
+                    state: container.state.as_str(),
+                    created: container.created,
+                    size_bytes,
+                })
+                .collect();
+
+            println!("{}", output::to_json(&entries));
+            return Ok(());
+        }
+
+        let table_rows: Vec<ContainerTableRow> = containers
+            .0
+            .iter()
+            .zip(sizes)
+            .map(|(container, size)| ContainerTableRow {
+                size: size.map(format_size).unwrap_or_default(),
+                ..container.into()
+            })
+            .collect();
+
+        let mut table = Table::new(table_rows);
+        if !self.size {
+            table.with(Remove::column(ByColumnName::new("size")));
+        }
 
         println!("{table}");
 
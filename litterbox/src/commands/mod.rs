@@ -7,8 +7,21 @@ mod define;
 mod delete;
 mod device;
 mod enter;
+mod export_def;
+mod import_def;
+mod inspect;
 mod keys;
 mod list;
+mod prune;
+mod rebuild_all;
+mod recreate;
+mod repair;
+mod run;
+mod settings;
+mod shell_init;
+mod ssh_config;
+mod template;
+mod update_check;
 
 #[derive(Subcommand, Debug)]
 pub enum Command {
@@ -25,12 +38,39 @@ pub enum Command {
 
     Enter(#[clap(flatten)] enter::Command),
 
+    ExportDef(#[clap(flatten)] export_def::Command),
+
+    ImportDef(#[clap(flatten)] import_def::Command),
+
+    Inspect(#[clap(flatten)] inspect::Command),
+
     #[clap(visible_alias("ls"))]
     List(#[clap(flatten)] list::Command),
 
     #[command(subcommand)]
     Keys(keys::Command),
 
+    Prune(#[clap(flatten)] prune::Command),
+
+    RebuildAll(#[clap(flatten)] rebuild_all::Command),
+
+    Recreate(#[clap(flatten)] recreate::Command),
+
+    Repair(#[clap(flatten)] repair::Command),
+
+    Run(#[clap(flatten)] run::Command),
+
+    Settings(#[clap(flatten)] settings::Command),
+
+    ShellInit(#[clap(flatten)] shell_init::Command),
+
+    SshConfig(#[clap(flatten)] ssh_config::Command),
+
+    #[command(subcommand)]
+    Template(template::Command),
+
+    UpdateCheck(#[clap(flatten)] update_check::Command),
+
     #[clap(hide = true)]
     Confirm(#[clap(flatten)] confirm::Command),
 
@@ -45,8 +85,21 @@ impl Command {
             Command::Build(command) => command.run(),
             Command::List(command) => command.run(),
             Command::Enter(command) => command.run(),
+            Command::ExportDef(command) => command.run(),
+            Command::ImportDef(command) => command.run(),
+            Command::Inspect(command) => command.run(),
             Command::Delete(command) => command.run(),
             Command::Keys(command) => command.run(),
+            Command::Prune(command) => command.run(),
+            Command::RebuildAll(command) => command.run(),
+            Command::Recreate(command) => command.run(),
+            Command::Repair(command) => command.run(),
+            Command::Run(command) => command.run(),
+            Command::Settings(command) => command.run(),
+            Command::ShellInit(command) => command.run(),
+            Command::SshConfig(command) => command.run(),
+            Command::Template(command) => command.run(),
+            Command::UpdateCheck(command) => command.run(),
             Command::Device(command) => command.run(),
             Command::Confirm(command) => command.run(),
             Command::Daemon(command) => command.run(),
@@ -0,0 +1,23 @@
+use anyhow::Result;
+use clap::Args;
+use std::time::Duration;
+
+use crate::{podman::prune_litterboxes, utils::parse_duration};
+
+/// Delete Litterbox images that are no longer used by any container
+#[derive(Args, Debug)]
+pub struct Command {
+    /// Only prune what is older than this (e.g. 30d, 12h or 2w)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    since: Option<Duration>,
+
+    /// Also delete the home directories of Litterboxes that no longer exist
+    #[arg(long)]
+    homes: bool,
+}
+
+impl Command {
+    pub fn run(self) -> Result<()> {
+        prune_litterboxes(self.since, self.homes)
+    }
+}
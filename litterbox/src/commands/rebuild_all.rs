@@ -0,0 +1,27 @@
+use anyhow::Result;
+use clap::Args;
+
+use crate::{keys::Keys, podman::rebuild_all_litterboxes};
+
+/// Rebuild the images of all Litterboxes and recreate them, keeping homes and settings
+#[derive(Args, Debug)]
+pub struct Command {
+    /// Only rebuild the Litterboxes whose names match this glob pattern
+    pattern: Option<String>,
+
+    /// Set the password of the container user from a secret stored with `keys add-secret`
+    #[arg(long, value_name = "SECRET_NAME")]
+    password_from: Option<String>,
+}
+
+impl Command {
+    pub fn run(self) -> Result<()> {
+        // Asked for once up front so that the batch itself runs unattended
+        let user_password = match &self.password_from {
+            Some(secret_name) => Some(Keys::load()?.reveal_secret(secret_name)?),
+            None => None,
+        };
+
+        rebuild_all_litterboxes(self.pattern.as_deref(), user_password.as_deref())
+    }
+}
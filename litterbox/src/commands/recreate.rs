@@ -0,0 +1,17 @@
+use anyhow::Result;
+use clap::Args;
+
+use crate::podman::recreate_litterbox;
+
+/// Recreate the container of a Litterbox from its existing image and settings
+#[derive(Args, Debug)]
+pub struct Command {
+    /// The name of the Litterbox to recreate
+    name: String,
+}
+
+impl Command {
+    pub fn run(self) -> Result<()> {
+        recreate_litterbox(&self.name)
+    }
+}
@@ -0,0 +1,19 @@
+use anyhow::Result;
+use clap::Args;
+
+use crate::podman::repair_litterbox;
+
+/// Resolve a Litterbox that has multiple containers or images
+#[derive(Args, Debug)]
+pub struct Command {
+    /// The name of the Litterbox to repair
+    name: String,
+}
+
+impl Command {
+    pub fn run(self) -> Result<()> {
+        repair_litterbox(&self.name)?;
+
+        Ok(())
+    }
+}
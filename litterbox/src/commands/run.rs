@@ -0,0 +1,59 @@
+use anyhow::Result;
+use clap::Args;
+use log::info;
+use rand::RngExt;
+use shared::entrypoint::CommonEntrypointOptions;
+
+use crate::{
+    commands::enter::{ExecOptions, Interactive, Tty, enter_container},
+    podman::{build_image, build_litterbox, define_litterbox, purge_litterbox, require_container},
+};
+
+/// Removes the throwaway Litterbox when dropped, regardless of how the session
+/// ended.
+struct EphemeralLitterbox {
+    name: String,
+}
+
+impl Drop for EphemeralLitterbox {
+    fn drop(&mut self) {
+        info!("Cleaning up throwaway Litterbox '{}'...", self.name);
+        purge_litterbox(&self.name);
+    }
+}
+
+/// Run a throwaway Litterbox that gets deleted when you exit it
+#[derive(Args, Debug)]
+pub struct Command {
+    /// The template to build the Litterbox from (see `template list`)
+    template: String,
+
+    #[clap(flatten)]
+    opts: CommonEntrypointOptions,
+}
+
+impl Command {
+    pub fn run(self) -> Result<()> {
+        let name = format!("tmp-{:08x}", rand::rng().random::<u32>());
+        eprintln!("Creating throwaway Litterbox '{name}'...");
+
+        define_litterbox(&name, Some(&self.template))?;
+        let ephemeral = EphemeralLitterbox { name };
+
+        let image_id = build_image(&ephemeral.name, &[], None, None, false)?;
+        build_litterbox(&ephemeral.name, &image_id, &[], &[], &[], None)?;
+
+        let container = require_container(&ephemeral.name)?;
+
+        enter_container(
+            &ephemeral.name,
+            container,
+            Interactive(false),
+            Tty(false),
+            ExecOptions::default(),
+            self.opts,
+        )?;
+
+        Ok(())
+    }
+}
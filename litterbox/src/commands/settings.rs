@@ -0,0 +1,33 @@
+use anyhow::Result;
+use clap::Args;
+
+use crate::settings::LitterboxSettings;
+
+/// Change the settings of a Litterbox without rebuilding it
+#[derive(Args, Debug)]
+pub struct Command {
+    /// The name of the Litterbox
+    name: String,
+
+    /// Only print the current settings
+    #[arg(long)]
+    show: bool,
+}
+
+impl Command {
+    pub fn run(self) -> Result<()> {
+        if self.show {
+            let settings = LitterboxSettings::require(&self.name)?;
+            println!("{}", settings.to_ron()?);
+            return Ok(());
+        }
+
+        LitterboxSettings::edit(&self.name)?;
+        eprintln!(
+            "Settings saved! Run `litterbox recreate {}` for them to take effect.",
+            self.name
+        );
+
+        Ok(())
+    }
+}
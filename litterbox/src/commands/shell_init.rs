@@ -0,0 +1,55 @@
+use anyhow::Result;
+use clap::Args;
+use std::path::Path;
+
+use crate::{
+    files::{lbx_home_path, ssh_sock_path},
+    podman::require_container,
+};
+
+/// Print shell `export` lines for a Litterbox, e.g. `eval "$(litterbox shell-init NAME)"`
+#[derive(Args, Debug)]
+pub struct Command {
+    /// The name of the Litterbox
+    name: String,
+}
+
+impl Command {
+    pub fn run(self) -> Result<()> {
+        require_container(&self.name)?;
+
+        // The agent socket only exists while the Litterbox is entered
+        let exports = [
+            ("LITTERBOX_NAME", self.name.clone()),
+            ("LITTERBOX_HOME", path_string(&lbx_home_path(&self.name)?)),
+            ("SSH_AUTH_SOCK", path_string(&ssh_sock_path(&self.name)?)),
+        ];
+
+        for (name, value) in exports {
+            println!("export {name}={}", shell_quote(&value));
+        }
+
+        Ok(())
+    }
+}
+
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+/// Quotes a value so that a POSIX shell reads it back literally
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_values_for_the_shell() {
+        assert_eq!(shell_quote("/home/me/Litterbox"), "'/home/me/Litterbox'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote("$HOME `x`"), "'$HOME `x`'");
+    }
+}
@@ -0,0 +1,40 @@
+use anyhow::Result;
+use clap::Args;
+
+use crate::{
+    files::ssh_sock_path,
+    podman::{LBX_USER, require_container},
+    utils::podman_name,
+};
+
+/// Print an SSH config block for connecting to a Litterbox that runs sshd
+#[derive(Args, Debug)]
+pub struct Command {
+    /// The name of the Litterbox
+    name: String,
+
+    /// The host port that sshd inside the Litterbox is reachable on
+    #[arg(long, default_value_t = 2222)]
+    port: u16,
+}
+
+impl Command {
+    pub fn run(self) -> Result<()> {
+        require_container(&self.name)?;
+
+        let host = podman_name(&self.name);
+        let agent_sock = ssh_sock_path(&self.name)?;
+
+        // The agent socket only exists while the Litterbox is entered, so
+        // connections only have access to the attached keys at that time.
+        println!("Host {host}");
+        println!("    HostName localhost");
+        println!("    Port {}", self.port);
+        println!("    User {LBX_USER}");
+        println!("    HostKeyAlias {host}");
+        println!("    ForwardAgent yes");
+        println!("    IdentityAgent {}", agent_sock.display());
+
+        Ok(())
+    }
+}
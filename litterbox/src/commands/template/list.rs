@@ -0,0 +1,30 @@
+use anyhow::Result;
+use clap::Args;
+use tabled::{Table, Tabled};
+
+use crate::template::all_templates;
+
+#[derive(Tabled)]
+struct TemplateTableRow {
+    name: String,
+    description: String,
+}
+
+/// List the built-in templates and the ones in ~/Litterbox/templates
+#[derive(Args, Debug)]
+pub struct Command {}
+
+impl Command {
+    pub fn run(self) -> Result<()> {
+        let rows: Vec<TemplateTableRow> = all_templates()?
+            .into_iter()
+            .map(|template| TemplateTableRow {
+                name: template.name,
+                description: template.description,
+            })
+            .collect();
+
+        println!("{}", Table::new(rows));
+        Ok(())
+    }
+}
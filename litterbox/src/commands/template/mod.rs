@@ -0,0 +1,23 @@
+use anyhow::Result;
+use clap::Subcommand;
+
+mod list;
+mod show;
+
+/// Discover the templates that Litterboxes can be defined from
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    #[clap(visible_alias("ls"))]
+    List(#[clap(flatten)] list::Command),
+
+    Show(#[clap(flatten)] show::Command),
+}
+
+impl Command {
+    pub fn run(self) -> Result<()> {
+        match self {
+            Command::List(command) => command.run(),
+            Command::Show(command) => command.run(),
+        }
+    }
+}
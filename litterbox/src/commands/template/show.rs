@@ -0,0 +1,19 @@
+use anyhow::Result;
+use clap::Args;
+
+use crate::template::template_contents;
+
+/// Print the Dockerfile of a template
+#[derive(Args, Debug)]
+pub struct Command {
+    /// The name of the template, as shown by `template list`
+    name: String,
+}
+
+impl Command {
+    pub fn run(self) -> Result<()> {
+        print!("{}", template_contents(&self.name)?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,24 @@
+use anyhow::Result;
+use clap::Args;
+
+use crate::update::available_update;
+
+/// Check whether a newer version of Litterbox has been released
+#[derive(Args, Debug)]
+pub struct Command {
+    /// Ask GitHub again even if it was already asked within the last day
+    #[arg(long)]
+    refresh: bool,
+}
+
+impl Command {
+    pub fn run(self) -> Result<()> {
+        let current = env!("CARGO_PKG_VERSION");
+        match available_update(self.refresh)? {
+            Some(latest) => println!("Litterbox {latest} is available (you have {current})."),
+            None => println!("Litterbox {current} is up to date."),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::{Context, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    files::{config_path, read_file},
+    settings::NetworkMode,
+};
+
+/// Preferences that apply to all Litterboxes, read from `~/Litterbox/config.ron`.
+/// Unlike the settings of a Litterbox, these are never prompted for.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct GlobalConfig {
+    /// Network mode that is pre-selected when choosing the settings of a new
+    /// Litterbox
+    #[serde(default)]
+    pub default_network_mode: Option<NetworkMode>,
+    /// Key sequence for detaching from `enter` sessions, e.g. `ctrl-a,d`,
+    /// unless one is given with `--detach-keys`
+    #[serde(default)]
+    pub detach_keys: Option<String>,
+    /// Whether every command mentions when a newer version is available,
+    /// checking GitHub at most once a day
+    #[serde(default)]
+    pub check_for_updates: bool,
+}
+
+impl GlobalConfig {
+    pub fn load() -> Result<Self> {
+        let path = config_path()?;
+        if !path.exists() {
+            debug!("No global config at {path:?}, using the defaults");
+            return Ok(Self::default());
+        }
+
+        Self::parse(&read_file(&path)?).with_context(|| format!("Invalid config file {path:?}"))
+    }
+
+    fn parse(contents: &str) -> Result<Self> {
+        Ok(ron::from_str(contents)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_config() {
+        let config = GlobalConfig::parse("(default_network_mode: Some(Host))").unwrap();
+        assert_eq!(config.default_network_mode, Some(NetworkMode::Host));
+
+        let config = GlobalConfig::parse("()").unwrap();
+        assert_eq!(config.default_network_mode, None);
+        assert_eq!(config.detach_keys, None);
+        assert!(!config.check_for_updates);
+
+        let config = GlobalConfig::parse("(detach_keys: Some(\"ctrl-a,d\"))").unwrap();
+        assert_eq!(config.detach_keys.as_deref(), Some("ctrl-a,d"));
+
+        assert!(GlobalConfig::parse("(default_network_mode: Some(Bridge))").is_err());
+    }
+}
@@ -1,11 +1,12 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, ensure};
 use log::info;
 use nix::sys::signal::kill;
 use nix::unistd::Pid;
+use std::time::{Duration, Instant};
 
 use crate::files;
 use crate::keys::Keys;
-use crate::podman::is_container_running;
+use crate::podman;
 
 pub async fn run(lbx_name: &str, password: &str) -> Result<()> {
     let daemon_lock = files::daemon_lock_path(lbx_name)?;
@@ -29,7 +30,7 @@ pub async fn run(lbx_name: &str, password: &str) -> Result<()> {
     std::fs::write(&daemon_lock, my_pid.to_string()).context("Failed to write daemon lock file")?;
 
     let keys = Keys::load()?;
-    keys.start_ssh_server(lbx_name, password).await?;
+    let agent = keys.start_ssh_server(lbx_name, password).await?;
 
     let session_path = files::session_lock_path(lbx_name)?;
 
@@ -38,12 +39,14 @@ pub async fn run(lbx_name: &str, password: &str) -> Result<()> {
 
         files::cleanup_dead_pids_from_session_lockfile(&session_path)?;
 
-        if !is_container_running(lbx_name)? {
+        if !podman::is_running(lbx_name)? {
             info!("Container no longer running, daemon will stop.");
             break;
         }
     }
 
+    agent.shutdown().await;
+
     if session_path.exists() {
         info!("Cleaning up session lockfile.");
         std::fs::remove_file(&session_path).context("Failed to remove session lock file")?;
@@ -54,6 +57,25 @@ pub async fn run(lbx_name: &str, password: &str) -> Result<()> {
     Ok(())
 }
 
+/// How long the daemon may take to notice that its container has stopped and
+/// to let the agent finish the requests that are still in flight
+const STOP_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Waits for the daemon to exit once its container has been stopped, so that
+/// the agent socket is free again for a new container.
+pub fn wait_for_exit(lbx_name: &str) -> Result<()> {
+    let start = Instant::now();
+    while is_running(lbx_name)? {
+        ensure!(
+            start.elapsed() < STOP_TIMEOUT,
+            "The SSH agent of '{lbx_name}' is still running although its container was stopped."
+        );
+        std::thread::sleep(Duration::from_millis(250));
+    }
+
+    Ok(())
+}
+
 pub fn is_running(lbx_name: &str) -> Result<bool> {
     let daemon_lock = files::daemon_lock_path(lbx_name)?;
 
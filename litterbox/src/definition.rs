@@ -0,0 +1,116 @@
+use anyhow::{Context, Result, bail, ensure};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::{
+    files::{dockerfile_path, read_file, settings_path, write_file},
+    settings::LitterboxSettings,
+};
+
+/// Version of the bundle format, bumped when it changes incompatibly
+const BUNDLE_VERSION: u32 = 1;
+
+/// The Dockerfile and settings of a Litterbox in a single RON file that can be
+/// shared with others. Keys, secrets and host-specific settings are left out.
+#[derive(Debug, Deserialize, Serialize)]
+struct DefinitionBundle {
+    version: u32,
+    dockerfile: String,
+    /// Missing when the Litterbox had not been built yet
+    #[serde(default)]
+    settings: Option<LitterboxSettings>,
+}
+
+impl DefinitionBundle {
+    fn parse(contents: &str) -> Result<Self> {
+        let bundle: Self = ron::from_str(contents)?;
+        ensure!(
+            bundle.version <= BUNDLE_VERSION,
+            "The bundle was exported by a newer version of Litterbox (format {})",
+            bundle.version
+        );
+
+        Ok(bundle)
+    }
+}
+
+/// Writes the definition of a Litterbox to a bundle at `path`
+pub fn export_definition(lbx_name: &str, path: &Path) -> Result<()> {
+    let dockerfile = dockerfile_path(lbx_name)?;
+    if !dockerfile.exists() {
+        bail!("Litterbox '{lbx_name}' has not been defined");
+    }
+
+    let bundle = DefinitionBundle {
+        version: BUNDLE_VERSION,
+        dockerfile: read_file(&dockerfile)?,
+        settings: LitterboxSettings::load(lbx_name)?.map(|s| s.for_sharing()),
+    };
+    if bundle.settings.is_none() {
+        info!("'{lbx_name}' has no settings yet, only exporting its Dockerfile");
+    }
+
+    let contents = ron::ser::to_string_pretty(&bundle, ron::ser::PrettyConfig::default())
+        .context("Failed to serialise the definition")?;
+    write_file(path, &contents)
+}
+
+/// Defines a new Litterbox from a bundle at `path`, leaving the build to the user
+pub fn import_definition(path: &Path, lbx_name: &str) -> Result<()> {
+    let bundle = DefinitionBundle::parse(&read_file(path)?)
+        .with_context(|| format!("Invalid definition bundle {path:?}"))?;
+
+    let dockerfile = dockerfile_path(lbx_name)?;
+    if dockerfile.exists() {
+        bail!("Dockerfile already exists at {dockerfile:?}");
+    }
+    let settings = settings_path(lbx_name)?;
+    if settings.exists() {
+        bail!("Settings already exist at {settings:?}");
+    }
+
+    write_file(&dockerfile, &bundle.dockerfile)?;
+    info!("Dockerfile written to {dockerfile:?}");
+    if let Some(settings) = bundle.settings {
+        settings.save_to_file(lbx_name)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundles_leave_out_keys_and_host_specific_settings() {
+        let settings: LitterboxSettings = ron::from_str(
+            "(version: 1, support_ping: true, support_tuntap: false, \
+             packet_forwarding: false, expose_pipewire: true, \
+             key_policy: ({\"deploy\": [Sign]}), post_create_hook: Some(\"/home/me/hook.sh\"), \
+             attached_devices: [(path: \"/dev/ttyUSB0\", owner: \"1000:1000\", perms: \"0660\")])",
+        )
+        .unwrap();
+
+        let bundle = DefinitionBundle {
+            version: BUNDLE_VERSION,
+            dockerfile: "FROM alpine\n".to_owned(),
+            settings: Some(settings.for_sharing()),
+        };
+        let contents = ron::ser::to_string(&bundle).unwrap();
+        assert!(!contents.contains("deploy"), "{contents}");
+        assert!(!contents.contains("ttyUSB0"), "{contents}");
+        assert!(!contents.contains("hook.sh"), "{contents}");
+
+        let parsed = DefinitionBundle::parse(&contents).unwrap();
+        let settings = parsed.settings.unwrap();
+        assert_eq!(parsed.dockerfile, "FROM alpine\n");
+        assert!(settings.support_ping);
+        assert!(settings.expose_pipewire);
+
+        let parsed = DefinitionBundle::parse("(version: 1, dockerfile: \"FROM alpine\")").unwrap();
+        assert!(parsed.settings.is_none());
+        assert!(DefinitionBundle::parse("(version: 2, dockerfile: \"FROM alpine\")").is_err());
+    }
+}
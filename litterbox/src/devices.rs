@@ -1,34 +1,110 @@
-use anyhow::{Context, Result, ensure};
-use log::{debug, info};
+use anyhow::{Context, Result, anyhow, bail, ensure};
+use log::{debug, info, warn};
 use nix::sys::stat::{SFlag, major, minor, stat};
+use nix::unistd::{AccessFlags, access, getgid, getuid};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
-use crate::{files::lbx_home_path, utils::trace_arguments};
+use crate::{
+    files::lbx_home_path,
+    settings::{AttachedDevice, LitterboxSettings},
+    utils::trace_arguments,
+};
 
-fn mknod(major_num: u64, minor_num: u64, dev_type: &str, path: &Path) -> Result<()> {
+/// Permissions that attached device nodes get unless others are requested
+pub const DEFAULT_DEVICE_PERMS: &str = "0660";
+
+/// The host user, which is also the user inside the container under keep-id
+pub fn default_device_owner() -> String {
+    format!("{}:{}", getuid(), getgid())
+}
+
+/// Validates an owner given as numeric `uid:gid`
+pub fn parse_owner(owner: &str) -> Result<String> {
+    let (uid, gid) = owner
+        .split_once(':')
+        .ok_or_else(|| anyhow!("Owner \"{owner}\" should be in the form UID:GID"))?;
+
+    ensure!(
+        uid.parse::<u32>().is_ok() && gid.parse::<u32>().is_ok(),
+        "Owner \"{owner}\" should use a numeric UID and GID"
+    );
+
+    Ok(owner.to_owned())
+}
+
+/// Validates permissions given as an octal mode such as `0660`
+pub fn parse_perms(perms: &str) -> Result<String> {
+    let mode = u32::from_str_radix(perms, 8)
+        .map_err(|_| anyhow!("Permissions \"{perms}\" should be an octal mode like 0660"))?;
+
+    ensure!(
+        mode <= 0o7777,
+        "Permissions \"{perms}\" are out of range for a file mode"
+    );
+
+    Ok(perms.to_owned())
+}
+
+/// Validates the path of a host device, which must live under `/dev/`
+pub fn parse_device_path(path: &str) -> Result<String> {
+    ensure!(
+        path.strip_prefix("/dev/").is_some_and(|p| !p.is_empty()),
+        "Invalid device path \"{path}\", expected something like /dev/ttyUSB0"
+    );
+
+    Ok(path.to_owned())
+}
+
+/// Runs a command that needs root permissions through `sudo`
+fn sudo(args: &[&str]) -> Result<()> {
+    let mut cmd = Command::new("sudo");
+    cmd.args(args);
+    let _span = trace_arguments(&cmd);
+    let mut child = cmd
+        .spawn()
+        .with_context(|| format!("Failed to run {} command", args[0]))?;
+    let res = child
+        .wait()
+        .with_context(|| format!("Failed to run {} command", args[0]))?;
+
+    ensure!(res.success(), "{} command failed", args[0]);
+    Ok(())
+}
+
+/// Asks for the password once up front so that `sudo` does not prompt again
+/// for each of the `count` device nodes about to be created
+fn request_root(count: usize) -> Result<()> {
+    let nodes = if count == 1 {
+        "a device node".to_owned()
+    } else {
+        format!("{count} device nodes")
+    };
     eprintln!(
-        "Root permissions are required to create a device node. Please enter your password if prompted."
+        "Root permissions are required to create {nodes}. Please enter your password if prompted."
     );
 
     let mut cmd = Command::new("sudo");
-    cmd.args([
+    cmd.arg("-v");
+    let _span = trace_arguments(&cmd);
+    let res = cmd.status().context("Failed to run sudo")?;
+    ensure!(res.success(), "Could not get root permissions through sudo");
+    Ok(())
+}
+
+fn mknod(major_num: u64, minor_num: u64, dev_type: &str, path: &Path) -> Result<()> {
+    sudo(&[
         "mknod",
         &path.to_string_lossy(), // TODO: maybe do something else instead?
         dev_type,
         &major_num.to_string(),
         &minor_num.to_string(),
-    ]);
-    trace_arguments(&cmd);
-    let mut child = cmd.spawn().context("Failed to run mknod command")?;
-    let res = child.wait().context("Failed to run mknod command")?;
-
-    ensure!(res.success(), "mknod command failed");
-    Ok(())
+    ])
 }
 
-pub fn attach_device(lbx_name: &str, device_path: &str) -> Result<PathBuf> {
+/// Where the node for `device_path` gets created inside the home directory
+fn device_dest_path(lbx_name: &str, device_path: &str) -> Result<PathBuf> {
     let sub_path = device_path
         .strip_prefix("/dev/")
         .with_context(|| format!("Invalid device path: {device_path}"))?;
@@ -36,7 +112,116 @@ pub fn attach_device(lbx_name: &str, device_path: &str) -> Result<PathBuf> {
 
     let lbx_path = lbx_home_path(lbx_name)?;
     debug!("lbx_path: {:#?}", lbx_path);
-    let dest_path = lbx_path.join("dev").join(sub_path);
+    Ok(lbx_path.join("dev").join(sub_path))
+}
+
+/// Creates a node for each device in the home directory with the given owner
+/// and permissions, and records them in the settings so that they can be
+/// restored. The password for `sudo` is only asked for once. Returns the
+/// outcome for each device so that one failure does not hide the others.
+pub fn attach_devices(
+    lbx_name: &str,
+    devices: Vec<AttachedDevice>,
+) -> Result<Vec<(String, Result<PathBuf>)>> {
+    let mut settings = LitterboxSettings::load(lbx_name)?;
+    if settings.as_ref().is_some_and(|s| !s.mount_home) {
+        bail!("Devices can only be attached to Litterboxes with a home directory on the host.");
+    }
+
+    request_root(devices.len())?;
+
+    let mut results = Vec::new();
+    for device in devices {
+        let path = device.path.clone();
+        let result = create_device_node(lbx_name, &device).and_then(|dest_path| {
+            match &mut settings {
+                Some(settings) => settings.record_device(lbx_name, device)?,
+                None => {
+                    warn!("No settings exist for '{lbx_name}' yet, so {path} was not recorded.")
+                }
+            }
+            Ok(dest_path)
+        });
+        results.push((path, result));
+    }
+
+    Ok(results)
+}
+
+/// Prints the outcome of each attached device, failing if any of them could
+/// not be attached
+pub fn report_attached(results: Vec<(String, Result<PathBuf>)>) -> Result<()> {
+    let mut failures = 0;
+    for (path, result) in &results {
+        match result {
+            Ok(dest_path) => eprintln!("Device {path} attached at {dest_path:?}!"),
+            Err(e) => {
+                failures += 1;
+                eprintln!("Failed to attach {path}: {e:#}");
+            }
+        }
+    }
+
+    ensure!(
+        failures == 0,
+        "{failures} of {} devices could not be attached",
+        results.len()
+    );
+    Ok(())
+}
+
+/// Splits the devices requested for a build into the ones that can be passed
+/// through when the container gets created and the paths of the ones that
+/// need a node in the home directory. Passing a device through needs no root
+/// permissions, but only works when the host user can already access it.
+pub fn split_build_devices(device_paths: &[String]) -> (Vec<AttachedDevice>, Vec<String>) {
+    let mut passthrough = Vec::new();
+    let mut needs_node = Vec::new();
+
+    for device_path in device_paths {
+        if access(device_path.as_str(), AccessFlags::R_OK | AccessFlags::W_OK).is_ok() {
+            passthrough.push(AttachedDevice {
+                path: device_path.clone(),
+                owner: default_device_owner(),
+                perms: DEFAULT_DEVICE_PERMS.to_owned(),
+                passthrough: true,
+            });
+        } else {
+            debug!("{device_path} is not accessible, so it needs a device node");
+            needs_node.push(device_path.clone());
+        }
+    }
+
+    (passthrough, needs_node)
+}
+
+/// Attaches devices with the default owner and permissions as part of a
+/// build. Devices whose nodes already exist are left alone.
+pub fn attach_devices_on_build(lbx_name: &str, device_paths: &[String]) -> Result<()> {
+    let mut devices = Vec::new();
+    for device_path in device_paths {
+        if device_dest_path(lbx_name, device_path)?.exists() {
+            debug!("{device_path} is already attached to {lbx_name}");
+            continue;
+        }
+
+        devices.push(AttachedDevice {
+            path: device_path.clone(),
+            owner: default_device_owner(),
+            perms: DEFAULT_DEVICE_PERMS.to_owned(),
+            passthrough: false,
+        });
+    }
+
+    if devices.is_empty() {
+        return Ok(());
+    }
+    report_attached(attach_devices(lbx_name, devices)?)
+}
+
+fn create_device_node(lbx_name: &str, device: &AttachedDevice) -> Result<PathBuf> {
+    let device_path = device.path.as_str();
+    let dest_path = device_dest_path(lbx_name, device_path)?;
     debug!("dest_path: {:#?}", dest_path);
 
     let metadata = stat(device_path).context("Failed to stat device")?;
@@ -65,6 +250,71 @@ pub fn attach_device(lbx_name: &str, device_path: &str) -> Result<PathBuf> {
     debug!("Output dir ready!");
 
     mknod(major_num, minor_num, dev_type, &dest_path)?;
-    // TODO: maybe we also need to set the owner and permissions
+
+    let dest = dest_path.to_string_lossy();
+    sudo(&["chown", &device.owner, &dest])?;
+    sudo(&["chmod", &device.perms, &dest])?;
+    info!(
+        "Device owned by {} with permissions {}",
+        device.owner, device.perms
+    );
+
     Ok(dest_path)
 }
+
+/// Re-creates the nodes of recorded devices that have gone missing from the
+/// home directory, using the owner and permissions they were attached with.
+pub fn restore_devices(lbx_name: &str, settings: &LitterboxSettings) -> Result<()> {
+    let mut missing = Vec::new();
+    for device in settings.attached_devices.iter().filter(|d| !d.passthrough) {
+        if !device_dest_path(lbx_name, &device.path)?.exists() {
+            missing.push(device);
+        }
+    }
+
+    if missing.is_empty() {
+        return Ok(());
+    }
+    request_root(missing.len())?;
+
+    for device in missing {
+        eprintln!("Restoring device {}...", device.path);
+        create_device_node(lbx_name, device)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_owner_and_perms() {
+        assert_eq!(parse_owner("1000:1000").unwrap(), "1000:1000");
+        assert!(parse_owner("1000").is_err());
+        assert!(parse_owner("user:video").is_err());
+        assert!(parse_owner("1000:").is_err());
+
+        assert_eq!(parse_perms("0660").unwrap(), "0660");
+        assert_eq!(parse_perms("600").unwrap(), "600");
+        assert!(parse_perms("0990").is_err());
+        assert!(parse_perms("rw-rw----").is_err());
+        assert!(parse_perms("17777").is_err());
+
+        assert_eq!(parse_device_path("/dev/ttyUSB0").unwrap(), "/dev/ttyUSB0");
+        assert!(parse_device_path("/dev/").is_err());
+        assert!(parse_device_path("ttyUSB0").is_err());
+    }
+
+    #[test]
+    fn passes_through_accessible_devices() {
+        let paths = ["/dev/null".to_owned(), "/dev/lbx-missing".to_owned()];
+
+        let (passthrough, needs_node) = split_build_devices(&paths);
+        assert_eq!(passthrough.len(), 1);
+        assert_eq!(passthrough[0].path, "/dev/null");
+        assert!(passthrough[0].passthrough);
+        assert_eq!(needs_node, ["/dev/lbx-missing"]);
+    }
+}
@@ -1,6 +1,13 @@
 use anyhow::Result;
+use log::{debug, warn};
+use nix::unistd::{getgid, getuid};
 use shared::env::get_env;
-use std::path::PathBuf;
+use std::{
+    fs,
+    os::unix::fs::FileTypeExt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 pub use shared::env::xdg_runtime_dir;
 
@@ -8,10 +15,126 @@ pub fn home_dir() -> Result<PathBuf> {
     get_env("HOME").map(PathBuf::from)
 }
 
+/// UID and GID of the user running Litterbox on the host
+pub fn host_user_ids() -> (u32, u32) {
+    (getuid().as_raw(), getgid().as_raw())
+}
+
 pub fn wayland_display() -> Result<String> {
     get_env("WAYLAND_DISPLAY")
 }
 
+fn is_socket(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|m| m.file_type().is_socket())
+}
+
+/// Looks for the Wayland socket directly in the runtime directory and then in
+/// its subdirectories, since some compositors nest their sockets.
+fn find_wayland_socket(runtime_dir: &Path, display: &str) -> Option<PathBuf> {
+    let direct = runtime_dir.join(display);
+    if is_socket(&direct) {
+        return Some(direct);
+    }
+
+    fs::read_dir(runtime_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|entry| entry.path().join(display))
+        .find(|candidate| is_socket(candidate))
+}
+
+/// The host's Wayland socket is not a file that can be mounted, which is
+/// also the case when the compositor only listens on an abstract socket.
+#[derive(Debug)]
+pub struct WaylandSocketMissing {
+    pub display: String,
+    pub searched: PathBuf,
+}
+
+impl std::fmt::Display for WaylandSocketMissing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Could not find the Wayland socket \"{}\" as a socket file in {:?}. \
+             Without it the Litterbox would not have display access, since abstract sockets \
+             cannot be shared with a container. Set WAYLAND_DISPLAY to the full path of the \
+             socket file.",
+            self.display, self.searched
+        )
+    }
+}
+
+impl std::error::Error for WaylandSocketMissing {}
+
+/// Resolves the path of the host's Wayland socket. `WAYLAND_DISPLAY` may
+/// either be an absolute path or a name relative to `XDG_RUNTIME_DIR`.
+pub fn wayland_socket_path() -> Result<PathBuf> {
+    resolve_wayland_socket(&xdg_runtime_dir()?, &wayland_display()?)
+}
+
+fn resolve_wayland_socket(runtime_dir: &Path, display: &str) -> Result<PathBuf> {
+    let found = if Path::new(display).is_absolute() {
+        Some(PathBuf::from(display)).filter(|path| is_socket(path))
+    } else {
+        find_wayland_socket(runtime_dir, display)
+    };
+
+    match found {
+        Some(path) => {
+            debug!("Found Wayland socket at {path:?}");
+            Ok(path)
+        }
+        None => Err(WaylandSocketMissing {
+            display: display.to_owned(),
+            searched: runtime_dir.join(display),
+        }
+        .into()),
+    }
+}
+
+pub const PODMAN_TIMEOUT_VAR: &str = "LITTERBOX_PODMAN_TIMEOUT";
+const DEFAULT_PODMAN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long to wait for podman queries before giving up (in seconds)
+pub fn podman_timeout() -> Duration {
+    match std::env::var(PODMAN_TIMEOUT_VAR) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map(Duration::from_secs)
+            .unwrap_or_else(|_| {
+                warn!("Ignoring invalid {PODMAN_TIMEOUT_VAR} value: {value:?}");
+                DEFAULT_PODMAN_TIMEOUT
+            }),
+        Err(_) => DEFAULT_PODMAN_TIMEOUT,
+    }
+}
+
+pub const TRACE_VAR: &str = "LITTERBOX_TRACE";
+
+/// Whether the time spent in podman invocations and key handling should be
+/// printed, which is inherited by the daemon through the environment
+pub fn trace_enabled() -> bool {
+    std::env::var_os(TRACE_VAR).is_some_and(|value| !value.is_empty() && value != "0")
+}
+
+/// Detects the timezone of the host using `TZ` or the `/etc/localtime` symlink
+pub fn host_timezone() -> Option<String> {
+    if let Ok(tz) = std::env::var("TZ") {
+        let tz = tz.trim_start_matches(':');
+        if !tz.is_empty() {
+            return Some(tz.to_owned());
+        }
+    }
+
+    let target = std::fs::read_link("/etc/localtime").ok()?;
+    let target = target.to_string_lossy();
+    let (_, zone) = target.split_once("zoneinfo/")?;
+
+    Some(zone.to_owned())
+}
+
 pub fn litterbox_binary_path() -> PathBuf {
     std::env::current_exe().expect("Binary path should be defined.")
 }
@@ -22,3 +145,43 @@ pub fn lbx_init_binary_path() -> PathBuf {
         .expect("Litterbox binary path should have a parent")
         .join("lbx-init")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_nested_wayland_socket() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let runtime_dir = dir.path();
+        let nested = runtime_dir.join("compositor");
+        fs::create_dir_all(&nested).unwrap();
+
+        assert_eq!(find_wayland_socket(runtime_dir, "wayland-1"), None);
+
+        let _listener = std::os::unix::net::UnixListener::bind(nested.join("wayland-1")).unwrap();
+        assert_eq!(
+            find_wayland_socket(runtime_dir, "wayland-1"),
+            Some(nested.join("wayland-1"))
+        );
+    }
+
+    #[test]
+    fn rejects_wayland_display_without_socket_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let runtime_dir = dir.path();
+
+        // A regular file is not a socket either
+        fs::write(runtime_dir.join("wayland-0"), "").unwrap();
+
+        for display in ["wayland-0", "wayland-9"] {
+            let err = resolve_wayland_socket(runtime_dir, display).unwrap_err();
+            let missing = err.downcast_ref::<WaylandSocketMissing>().unwrap();
+            assert_eq!(missing.display, display);
+        }
+
+        let absolute = runtime_dir.join("wayland-0");
+        let err = resolve_wayland_socket(runtime_dir, absolute.to_str().unwrap()).unwrap_err();
+        assert!(err.is::<WaylandSocketMissing>());
+    }
+}
@@ -1,6 +1,8 @@
 use anyhow::{Context, Result};
 use nix::unistd::Pid;
 use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
 
 use crate::env;
@@ -13,6 +15,16 @@ fn path_relative_to_lbx_root(relative_path: &str) -> Result<PathBuf> {
     Ok(full_path)
 }
 
+/// Global preferences that apply to all Litterboxes
+pub fn config_path() -> Result<PathBuf> {
+    path_relative_to_lbx_root("config.ron")
+}
+
+/// Directory with the user's own templates, one `NAME.Dockerfile` each
+pub fn custom_templates_path() -> Result<PathBuf> {
+    path_relative_to_lbx_root("templates")
+}
+
 pub fn dockerfile_path(lbx_name: &str) -> Result<PathBuf> {
     path_relative_to_lbx_root(&format!("definitions/{lbx_name}.Dockerfile"))
 }
@@ -25,25 +37,63 @@ pub fn lbx_home_path(lbx_name: &str) -> Result<PathBuf> {
     path_relative_to_lbx_root(&format!("homes/{lbx_name}"))
 }
 
+/// Directory that holds the home directories of all Litterboxes
+pub fn homes_path() -> Result<PathBuf> {
+    path_relative_to_lbx_root("homes")
+}
+
+/// Where the container details that udica reads and the SELinux policies it
+/// writes are kept
+pub fn selinux_policies_path() -> Result<PathBuf> {
+    path_relative_to_lbx_root(".selinux")
+}
+
 pub fn settings_path(lbx_name: &str) -> Result<PathBuf> {
     path_relative_to_lbx_root(&format!("definitions/{lbx_name}.ron"))
 }
 
+pub fn ssh_sock_path(lbx_name: &str) -> Result<PathBuf> {
+    path_relative_to_lbx_root(&format!(".ssh/{lbx_name}.sock"))
+}
+
+pub fn audit_log_path(lbx_name: &str) -> Result<PathBuf> {
+    path_relative_to_lbx_root(&format!(".ssh/{lbx_name}.audit.jsonl"))
+}
+
+pub fn iid_path(lbx_name: &str) -> Result<PathBuf> {
+    path_relative_to_lbx_root(&format!(".build-{lbx_name}.iid"))
+}
+
+/// Cache of the latest released version, see `litterbox update-check`
+pub fn update_check_path() -> Result<PathBuf> {
+    path_relative_to_lbx_root(".update-check.ron")
+}
+
 pub fn daemon_lock_path(lbx_name: &str) -> Result<PathBuf> {
     path_relative_to_lbx_root(&format!(".daemon-{lbx_name}.lock"))
 }
 
+/// Marks that the post-create hook of a Litterbox has run
+pub fn post_create_hook_marker_path(lbx_name: &str) -> Result<PathBuf> {
+    path_relative_to_lbx_root(&format!(".hook-{lbx_name}.done"))
+}
+
 pub fn session_lock_path(lbx_name: &str) -> Result<PathBuf> {
     path_relative_to_lbx_root(&format!(".session-{lbx_name}.lock"))
 }
 
+/// Sessions entered with `--no-agent`, which keep the agent from starting
+pub fn no_agent_lock_path(lbx_name: &str) -> Result<PathBuf> {
+    path_relative_to_lbx_root(&format!(".no-agent-{lbx_name}.lock"))
+}
+
 pub fn daemon_log_file(lbx_name: &str) -> Result<File> {
     let path = path_relative_to_lbx_root(&format!("logs/daemon-{lbx_name}.log"))?;
     let output_dir = path.parent().expect("Path should have parent.");
 
-    fs::create_dir_all(output_dir)?;
+    create_dir(output_dir)?;
 
-    File::create(&path).context("Could not create daemon log file")
+    File::create(&path).with_context(|| format!("Could not create daemon log file {path:?}"))
 }
 
 pub fn append_pid_to_session_lockfile(path: &Path, pid: Pid) -> Result<()> {
@@ -74,7 +124,10 @@ pub fn write_pids_to_session_lockfile(path: &Path, pids: &[Pid]) -> Result<()> {
         .collect::<Vec<_>>()
         .join("\n");
 
-    write_file(path, &content)
+    // The lock file is bind mounted into the container, so it must be written
+    // in place. Replacing it would leave the container looking at the old one.
+    create_dir(path.parent().expect("Path should have parent."))?;
+    fs::write(path, content).with_context(|| format!("Could not write file {path:?}"))
 }
 
 pub fn read_pids_from_session_lockfile(path: &Path) -> Result<Vec<Pid>> {
@@ -119,6 +172,26 @@ pub fn cleanup_dead_pids_from_session_lockfile(path: &Path) -> Result<()> {
     Ok(())
 }
 
+pub fn host_gitconfig_path() -> Result<PathBuf> {
+    Ok(env::home_dir()?.join(".gitconfig"))
+}
+
+pub fn host_ssh_dir_path() -> Result<PathBuf> {
+    Ok(env::home_dir()?.join(".ssh"))
+}
+
+pub fn host_known_hosts_path() -> Result<PathBuf> {
+    Ok(host_ssh_dir_path()?.join("known_hosts"))
+}
+
+/// The user's D-Bus session bus, used for things like notifications
+pub fn dbus_socket_path() -> Result<PathBuf> {
+    let mut xdg_runtime_dir = env::xdg_runtime_dir()?;
+    xdg_runtime_dir.push("bus");
+
+    Ok(xdg_runtime_dir)
+}
+
 pub fn pipewire_socket_path() -> Result<PathBuf> {
     let mut xdg_runtime_dir = env::xdg_runtime_dir()?;
     xdg_runtime_dir.push("pipewire-0");
@@ -126,35 +199,166 @@ pub fn pipewire_socket_path() -> Result<PathBuf> {
     Ok(xdg_runtime_dir)
 }
 
+/// Writes a file atomically by writing to `<path>.tmp` first and renaming it
+/// into place, so that an interrupted write cannot leave a truncated file.
+/// The permissions of an existing file are kept, and new files are only
+/// readable by the user.
 pub fn write_file(path: &Path, contents: &str) -> Result<()> {
     let output_dir = path.parent().expect("Path should have parent.");
-    fs::create_dir_all(output_dir)?;
-    fs::write(path, contents)?;
+    create_dir(output_dir)?;
+
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let permissions = match fs::metadata(path) {
+        Ok(metadata) => metadata.permissions(),
+        Err(e) if e.kind() == ErrorKind::NotFound => fs::Permissions::from_mode(0o600),
+        Err(e) => return Err(e).with_context(|| format!("Could not read metadata of {path:?}")),
+    };
+
+    let write = || -> std::io::Result<()> {
+        let mut file = File::create(&tmp_path)?;
+        // Also applies to a `.tmp` file left behind by an interrupted write
+        file.set_permissions(permissions)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()
+    };
+    write().with_context(|| format!("Failed to write temporary file {tmp_path:?}"))?;
+    fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to move {tmp_path:?} into place"))?;
     Ok(())
 }
 
 pub fn read_file(path: &Path) -> Result<String> {
-    Ok(fs::read_to_string(path)?)
+    fs::read_to_string(path).with_context(|| format!("Could not read file {path:?}"))
+}
+
+/// Total size in bytes of the files under `path`, without following symlinks.
+/// Anything that cannot be read (e.g. due to permissions) is skipped.
+pub fn dir_size(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+
+    fs::read_dir(path)
+        .map(|entries| entries.flatten().map(|entry| dir_size(&entry.path())).sum())
+        .unwrap_or(0)
+}
+
+/// Copying the home directory of another Litterbox failed at `path`
+#[derive(Debug)]
+pub struct HomeCopyError {
+    pub path: PathBuf,
+    pub source: std::io::Error,
+}
+
+impl std::fmt::Display for HomeCopyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Could not copy {:?} into the new home directory",
+            self.path
+        )
+    }
+}
+
+impl std::error::Error for HomeCopyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Copies the contents of `src` into the directory `dest`, keeping symlinks as
+/// they are. Sockets, FIFOs and device nodes are skipped since copying them
+/// would not give a working copy anyway.
+pub fn copy_dir_contents(src: &Path, dest: &Path) -> Result<(), HomeCopyError> {
+    let copy_error = |path: &Path| {
+        let path = path.to_owned();
+        move |source: std::io::Error| HomeCopyError { path, source }
+    };
+
+    fs::create_dir_all(dest).map_err(copy_error(dest))?;
+    let permissions = fs::metadata(src).map_err(copy_error(src))?.permissions();
+    fs::set_permissions(dest, permissions).map_err(copy_error(dest))?;
+
+    for entry in fs::read_dir(src).map_err(copy_error(src))? {
+        let entry = entry.map_err(copy_error(src))?;
+        let from = entry.path();
+        let to = dest.join(entry.file_name());
+        let file_type = entry.file_type().map_err(copy_error(&from))?;
+
+        if file_type.is_dir() {
+            copy_dir_contents(&from, &to)?;
+        } else if file_type.is_symlink() {
+            let target = fs::read_link(&from).map_err(copy_error(&from))?;
+            std::os::unix::fs::symlink(target, &to).map_err(copy_error(&from))?;
+        } else if file_type.is_file() {
+            fs::copy(&from, &to).map_err(copy_error(&from))?;
+        } else {
+            log::debug!("Not copying special file {from:?}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates a directory and its parents, naming it if that fails
+fn create_dir(path: &Path) -> Result<()> {
+    fs::create_dir_all(path).with_context(|| format!("Could not create directory {path:?}"))
+}
+
+/// The SSH socket of a Litterbox is still being served by a running agent
+#[derive(Debug)]
+pub struct SocketInUse {
+    pub path: PathBuf,
 }
 
+impl std::fmt::Display for SocketInUse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The SSH socket {:?} is in use by a running agent. Exit the sessions of this \
+             Litterbox first.",
+            self.path
+        )
+    }
+}
+
+impl std::error::Error for SocketInUse {}
+
 pub struct SshSockFile {
     path: PathBuf,
 }
 
 impl SshSockFile {
     pub fn new(lbx_name: &str, create_empty_placeholder: bool) -> Result<Self> {
-        let path = path_relative_to_lbx_root(&format!(".ssh/{lbx_name}.sock"))?;
+        Self::at(ssh_sock_path(lbx_name)?, create_empty_placeholder)
+    }
+
+    fn at(path: PathBuf, create_empty_placeholder: bool) -> Result<Self> {
         let path_ref = &path;
 
         if fs::exists(path_ref)? {
+            // Only stale sockets may be removed, never one that an agent for
+            // another session is still listening on.
+            if std::os::unix::net::UnixStream::connect(path_ref).is_ok() {
+                return Err(SocketInUse { path }.into());
+            }
+
             log::warn!("Deleting old SSH socket: {:#?}", path_ref);
-            fs::remove_file(path_ref)?;
+            fs::remove_file(path_ref)
+                .with_context(|| format!("Could not delete old SSH socket {path_ref:?}"))?;
         } else {
             let ssh_dir = path_ref.parent().expect("SSH path should have parent.");
-            fs::create_dir_all(ssh_dir)?;
+            create_dir(ssh_dir)?;
 
             if create_empty_placeholder {
-                fs::File::create(path_ref)?;
+                fs::File::create(path_ref)
+                    .with_context(|| format!("Could not create file {path_ref:?}"))?;
             }
         }
 
@@ -177,3 +381,99 @@ impl Drop for SshSockFile {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_errors_name_the_path() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("missing");
+
+        let err = read_file(&path).unwrap_err();
+        assert!(format!("{err}").contains(&*path.to_string_lossy()), "{err}");
+        assert!(err.root_cause().is::<std::io::Error>());
+    }
+
+    #[test]
+    fn writing_keeps_file_permissions() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        let new = dir.path().join("new.ron");
+        write_file(&new, "()").unwrap();
+        assert_eq!(mode(&new), 0o600);
+
+        let existing = dir.path().join("existing.ron");
+        fs::write(&existing, "old").unwrap();
+        fs::set_permissions(&existing, fs::Permissions::from_mode(0o640)).unwrap();
+        write_file(&existing, "new").unwrap();
+        assert_eq!(mode(&existing), 0o640);
+        assert_eq!(read_file(&existing).unwrap(), "new");
+    }
+
+    #[test]
+    fn sums_directory_sizes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(dir.path().join("a"), [0; 100]).unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub").join("b"), [0; 50]).unwrap();
+        std::os::unix::fs::symlink("/usr", dir.path().join("link")).unwrap();
+
+        let link_size = fs::symlink_metadata(dir.path().join("link")).unwrap().len();
+        assert_eq!(dir_size(dir.path()), 150 + link_size);
+        assert_eq!(dir_size(&dir.path().join("missing")), 0);
+    }
+
+    #[test]
+    fn copies_directories_without_special_files() {
+        let src = tempfile::TempDir::new().unwrap();
+        fs::write(src.path().join(".bashrc"), "alias ll='ls -l'").unwrap();
+        fs::create_dir(src.path().join("project")).unwrap();
+        fs::write(src.path().join("project").join("main.rs"), "fn main() {}").unwrap();
+        std::os::unix::fs::symlink("project/main.rs", src.path().join("link")).unwrap();
+        let _listener =
+            std::os::unix::net::UnixListener::bind(src.path().join("agent.sock")).unwrap();
+
+        let dest = tempfile::TempDir::new().unwrap();
+        let home = dest.path().join("home");
+        copy_dir_contents(src.path(), &home).unwrap();
+
+        assert_eq!(
+            read_file(&home.join(".bashrc")).unwrap(),
+            "alias ll='ls -l'"
+        );
+        assert_eq!(
+            read_file(&home.join("project").join("main.rs")).unwrap(),
+            "fn main() {}"
+        );
+        assert_eq!(
+            fs::read_link(home.join("link")).unwrap(),
+            Path::new("project/main.rs")
+        );
+        assert!(!home.join("agent.sock").exists());
+
+        let err = copy_dir_contents(&src.path().join("missing"), &home).unwrap_err();
+        assert_eq!(err.path, src.path().join("missing"));
+    }
+
+    #[test]
+    fn keeps_ssh_sockets_that_are_in_use() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("test.sock");
+
+        let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        let err = SshSockFile::at(path.clone(), false).err().unwrap();
+        assert!(err.is::<SocketInUse>());
+        assert!(path.exists());
+
+        // Nobody is listening anymore once the listener is gone
+        drop(listener);
+        let sock = SshSockFile::at(path.clone(), false).unwrap();
+        assert!(!path.exists());
+
+        // The socket was already removed, so there is nothing left to clean up
+        std::mem::forget(sock);
+    }
+}
@@ -1,9 +1,14 @@
+use aes_gcm::{
+    Aes256Gcm, KeyInit, Nonce,
+    aead::{Aead, OsRng, rand_core::RngCore},
+};
 use anyhow::{Context, Result, anyhow, bail};
 use argon2::Argon2;
-use inquire::{MultiSelect, Password};
+use inquire::{Confirm, MultiSelect, Password};
 use log::debug;
+use ron::ser::{PrettyConfig, to_string_pretty};
 use russh::keys::{
-    Algorithm, PrivateKey, decode_secret_key,
+    Algorithm, HashAlg, PrivateKey, PublicKey, decode_secret_key,
     pkcs8::{decode_pkcs8, encode_pkcs8_encrypted},
     ssh_key::LineEnding,
 };
@@ -16,8 +21,10 @@ use std::{
 use tabled::{Table, Tabled};
 
 use crate::{
-    agent::{AgentState, start_ssh_agent},
+    agent::{AgentHandle, AgentState, SignAutoApproval, UserRequest, start_ssh_agent},
     files,
+    settings::LitterboxSettings,
+    utils::{assume_yes, ensure_interactive},
 };
 
 fn generate_private_key() -> PrivateKey {
@@ -28,11 +35,64 @@ fn key_to_openssh(key: &PrivateKey) -> Result<String> {
     Ok(key.to_openssh(LineEnding::LF)?.to_string())
 }
 
-fn hash_password(password: &str) -> String {
+/// Cost parameters used when hashing the password and encrypting with it.
+/// Argon2 hashes and PKCS#8 keys record the parameters they were made with,
+/// so these only matter for new encryptions.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub struct KdfParams {
+    /// Argon2 memory cost in KiB
+    pub memory_kib: u32,
+    /// Argon2 number of passes
+    pub iterations: u32,
+    /// Argon2 degree of parallelism
+    pub parallelism: u32,
+    /// PBKDF2 rounds used for the PKCS#8 encryption of keys
+    pub pbkdf2_rounds: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            memory_kib: argon2::Params::DEFAULT_M_COST,
+            iterations: argon2::Params::DEFAULT_T_COST,
+            parallelism: argon2::Params::DEFAULT_P_COST,
+            pbkdf2_rounds: 10,
+        }
+    }
+}
+
+impl KdfParams {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn argon2(&self) -> Result<Argon2<'static>> {
+        let params = argon2::Params::new(self.memory_kib, self.iterations, self.parallelism, None)
+            .map_err(|e| anyhow!("Invalid argon2 parameters: {e}"))?;
+
+        Ok(Argon2::new(
+            argon2::Algorithm::default(),
+            argon2::Version::default(),
+            params,
+        ))
+    }
+
+    /// Checks that the parameters can actually be used
+    pub fn validate(&self) -> Result<()> {
+        self.argon2()?;
+        if self.pbkdf2_rounds == 0 {
+            bail!("The number of PBKDF2 rounds must be positive");
+        }
+
+        Ok(())
+    }
+}
+
+fn hash_password(password: &str, kdf: &KdfParams) -> String {
     use argon2::password_hash::{PasswordHasher, SaltString, rand_core::OsRng};
 
     let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
+    let argon2 = kdf.argon2().expect("KDF parameters should be valid");
 
     argon2
         .hash_password(password.as_bytes(), &salt)
@@ -40,10 +100,23 @@ fn hash_password(password: &str) -> String {
         .to_string()
 }
 
+/// Whether `hash` is an argon2 hash in the PHC string format
+fn is_valid_password_hash(hash: &str) -> bool {
+    use argon2::password_hash::PasswordHash;
+
+    PasswordHash::new(hash).is_ok_and(|parsed| parsed.algorithm.as_str().starts_with("argon2"))
+}
+
 fn check_password(password: &str, hash: &str) -> bool {
     use argon2::password_hash::{PasswordHash, PasswordVerifier};
 
-    let parsed_hash = PasswordHash::new(hash).expect("Passwords should have valid hashes");
+    let _span = tracing::info_span!("check_password").entered();
+
+    // The hash gets validated when loading, so this only fails for hashes that
+    // were never loaded from a file
+    let Ok(parsed_hash) = PasswordHash::new(hash) else {
+        return false;
+    };
 
     Argon2::default()
         .verify_password(password.as_bytes(), &parsed_hash)
@@ -55,31 +128,144 @@ struct Key {
     name: String,
     encrypted_key: Vec<u8>,
     attached_litterboxes: Vec<String>,
+    /// Whether the key gets attached to every newly built Litterbox. Left out
+    /// when false so that older checksums still match.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    default: bool,
+    /// The public key in OpenSSH format, so that it can be shown without
+    /// decrypting the key. Missing for keys added by older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    public_key: Option<String>,
 }
 
 impl Key {
-    fn new(name: &str, password: &str, private_key: &PrivateKey) -> Self {
+    /// Whether the key is not attached to any Litterbox
+    fn is_unused(&self) -> bool {
+        self.attached_litterboxes.is_empty()
+    }
+
+    /// The Litterboxes out of `litterbox_names` that the key is not attached to
+    fn unattached(&self, litterbox_names: Vec<String>) -> Vec<String> {
+        litterbox_names
+            .into_iter()
+            .filter(|name| !self.attached_litterboxes.contains(name))
+            .collect()
+    }
+
+    fn new(name: &str, password: &str, private_key: &PrivateKey, kdf: &KdfParams) -> Self {
         Self {
             name: name.to_owned(),
-            encrypted_key: Self::encrypt(private_key, password),
+            encrypted_key: Self::encrypt(private_key, password, kdf),
             attached_litterboxes: Vec::new(),
+            default: false,
+            public_key: private_key
+                .public_key()
+                .to_openssh()
+                .inspect_err(|e| log::warn!("Could not encode the public key of {name}: {e}"))
+                .ok(),
         }
     }
 
-    fn encrypt(private_key: &PrivateKey, password: &str) -> Vec<u8> {
-        encode_pkcs8_encrypted(password.as_bytes(), 10, private_key)
+    fn encrypt(private_key: &PrivateKey, password: &str, kdf: &KdfParams) -> Vec<u8> {
+        encode_pkcs8_encrypted(password.as_bytes(), kdf.pbkdf2_rounds, private_key)
             .expect("Keys should be encryptable")
     }
 
     fn decrypt(&self, password: &str) -> PrivateKey {
-        decode_pkcs8(&self.encrypted_key, Some(password.as_bytes()))
+        self.try_decrypt(password)
             .expect("Key should have been encrypted with user password")
     }
 
-    fn change_password(&mut self, old_password: &str, new_password: &str) {
+    /// Decrypts the key and names it with an OpenSSH comment, which PKCS#8
+    /// has no room for, so that printed and exported keys are recognisable.
+    fn try_decrypt(&self, password: &str) -> Result<PrivateKey> {
+        let _span = tracing::info_span!("decrypt_key", name = %self.name).entered();
+        let mut private_key = decode_pkcs8(&self.encrypted_key, Some(password.as_bytes()))
+            .with_context(|| format!("Key \"{}\" could not be decrypted", self.name))?;
+        private_key.set_comment(&self.name);
+
+        Ok(private_key)
+    }
+
+    fn change_password(&mut self, old_password: &str, new_password: &str, kdf: &KdfParams) {
         let decrypted = self.decrypt(old_password);
 
-        self.encrypted_key = Self::encrypt(&decrypted, new_password);
+        self.encrypted_key = Self::encrypt(&decrypted, new_password, kdf);
+    }
+}
+
+/// A named password (or other string) that is stored alongside the keys
+#[derive(Debug, Deserialize, Serialize)]
+struct Secret {
+    name: String,
+    salt: Vec<u8>,
+    nonce: Vec<u8>,
+    ciphertext: Vec<u8>,
+    /// The parameters the encryption key was derived with. Left out when they
+    /// are the defaults so that older checksums still match.
+    #[serde(default, skip_serializing_if = "KdfParams::is_default")]
+    kdf: KdfParams,
+}
+
+impl Secret {
+    fn new(name: &str, password: &str, value: &str, kdf: &KdfParams) -> Self {
+        let mut secret = Self {
+            name: name.to_owned(),
+            salt: Vec::new(),
+            nonce: Vec::new(),
+            ciphertext: Vec::new(),
+            kdf: *kdf,
+        };
+
+        secret.encrypt(value, password, kdf);
+        secret
+    }
+
+    /// Secrets are not PKCS#8 documents, so they cannot be encrypted like
+    /// keys are. They use AES-GCM with an argon2-derived key instead since,
+    /// unlike the AES-CBC of PKCS#8, it rejects a wrong password or a modified
+    /// ciphertext rather than decrypting to garbage.
+    fn cipher(salt: &[u8], password: &str, kdf: &KdfParams) -> Aes256Gcm {
+        let mut key = [0u8; 32];
+        kdf.argon2()
+            .expect("KDF parameters should be valid")
+            .hash_password_into(password.as_bytes(), salt, &mut key)
+            .expect("Secret keys should be derivable");
+
+        Aes256Gcm::new(&key.into())
+    }
+
+    fn encrypt(&mut self, value: &str, password: &str, kdf: &KdfParams) {
+        let mut salt = [0u8; 16];
+        let mut nonce = [0u8; 12];
+        OsRng.fill_bytes(&mut salt);
+        OsRng.fill_bytes(&mut nonce);
+
+        self.ciphertext = Self::cipher(&salt, password, kdf)
+            .encrypt(Nonce::from_slice(&nonce), value.as_bytes())
+            .expect("Secrets should be encryptable");
+        self.salt = salt.to_vec();
+        self.nonce = nonce.to_vec();
+        self.kdf = *kdf;
+    }
+
+    fn decrypt(&self, password: &str) -> Result<String> {
+        let plaintext = Self::cipher(&self.salt, password, &self.kdf)
+            .decrypt(Nonce::from_slice(&self.nonce), self.ciphertext.as_slice())
+            .map_err(|_| anyhow!("Secret \"{}\" could not be decrypted", self.name))?;
+
+        String::from_utf8(plaintext).context("Secret is not valid UTF-8")
+    }
+
+    fn change_password(
+        &mut self,
+        old_password: &str,
+        new_password: &str,
+        kdf: &KdfParams,
+    ) -> Result<()> {
+        let decrypted = self.decrypt(old_password)?;
+        self.encrypt(&decrypted, new_password, kdf);
+        Ok(())
     }
 }
 
@@ -87,6 +273,7 @@ impl Key {
 struct KeyTableRow {
     name: String,
     attached_litterboxes: String,
+    default: bool,
 }
 
 impl From<&Key> for KeyTableRow {
@@ -94,53 +281,206 @@ impl From<&Key> for KeyTableRow {
         Self {
             name: value.name.clone(),
             attached_litterboxes: value.attached_litterboxes.join(","),
+            default: value.default,
         }
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Oldest format of the keys file that can still be read
+const MIN_FORMAT_VERSION: u32 = 2;
+
+/// Format of the keys file written by this version of Litterbox
+const FORMAT_VERSION: u32 = 3;
+
+/// Just enough of the keys file to tell which format it uses, even when the
+/// rest of it cannot be parsed.
+#[derive(Deserialize)]
+struct KeysHeader {
+    #[serde(default, alias = "version")]
+    format_version: u32,
+}
+
+/// The keys file could be read, but holds something that cannot be used
+#[derive(Debug)]
+pub struct CorruptKeyfile {
+    pub path: PathBuf,
+    pub reason: &'static str,
+}
+
+impl std::fmt::Display for CorruptKeyfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Your key file ({}) is corrupt: {}.\nRestore it from a backup if you have one.",
+            self.path.display(),
+            self.reason
+        )
+    }
+}
+
+impl std::error::Error for CorruptKeyfile {}
+
+#[derive(Deserialize, Serialize)]
 pub struct Keys {
-    #[serde(default)]
-    version: u32,
+    #[serde(default, alias = "version")]
+    format_version: u32,
     password_hash: String,
     keys: Vec<Key>,
+    #[serde(default)]
+    secrets: Vec<Secret>,
+    /// Cost parameters for new hashes and encryptions. Left out when they are
+    /// the defaults so that older checksums still match.
+    #[serde(default, skip_serializing_if = "KdfParams::is_default")]
+    kdf: KdfParams,
+    /// SHA-256 of the file contents when serialised without this field
+    #[serde(default)]
+    checksum: Option<String>,
+    /// Where the keys get saved, or `None` to only keep them in memory
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    /// Password that was supplied up front, so it does not get prompted for
+    #[serde(skip)]
+    password: Option<String>,
+}
+
+// Written by hand to keep the password out of debug output
+impl std::fmt::Debug for Keys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Keys")
+            .field("format_version", &self.format_version)
+            .field("keys", &self.keys)
+            .field("secrets", &self.secrets)
+            .field("kdf", &self.kdf)
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
 }
 
 impl Keys {
     // TODO: perhaps we should place a lock on the keyfile while this struct exists?
 
-    fn save_to_file(&self) -> Result<()> {
-        let path = files::keyfile_path()?;
-        let contents = ron::ser::to_string(self).context("failed to serialise keys")?;
-        files::write_file(&path, &contents)
+    fn compute_checksum(&self) -> Result<String> {
+        use sha2::{Digest, Sha256};
+
+        // Everything but the checksum itself. Like in the file, the cost
+        // parameters are only included when they are not the defaults.
+        let contents = if self.kdf.is_default() {
+            ron::ser::to_string(&(
+                self.format_version,
+                &self.password_hash,
+                &self.keys,
+                &self.secrets,
+            ))
+        } else {
+            ron::ser::to_string(&(
+                self.format_version,
+                &self.password_hash,
+                &self.keys,
+                &self.secrets,
+                &self.kdf,
+            ))
+        }
+        .context("failed to serialise keys")?;
+
+        Ok(format!("{:x}", Sha256::digest(contents)))
+    }
+
+    fn save_to_file(&mut self) -> Result<()> {
+        // Files get upgraded to the current format whenever they are written
+        self.format_version = FORMAT_VERSION;
+        self.checksum = Some(self.compute_checksum()?);
+
+        let Some(path) = &self.path else {
+            debug!("Keys are only kept in memory, not saving them.");
+            return Ok(());
+        };
+
+        // Pretty like the settings so that attachments can be inspected by
+        // hand, but with the encrypted bytes kept on a single line.
+        let contents = to_string_pretty(self, PrettyConfig::default().compact_arrays(true))
+            .context("failed to serialise keys")?;
+        files::write_file(path, &contents)
+    }
+
+    /// Saves the keys to `path`, which is also where they get saved from now on.
+    pub fn save_to(&mut self, path: &Path) -> Result<()> {
+        self.path = Some(path.to_owned());
+        self.save_to_file()
+    }
+
+    /// Creates an empty set of keys protected by `password` without touching
+    /// the disk. Nothing gets saved unless `save_to` is called.
+    pub fn new_in_memory(password: &str, kdf: KdfParams) -> Result<Self> {
+        kdf.validate()?;
+
+        Ok(Self {
+            format_version: FORMAT_VERSION,
+            password_hash: hash_password(password, &kdf),
+            keys: Vec::new(),
+            secrets: Vec::new(),
+            kdf,
+            checksum: None,
+            path: None,
+            password: Some(password.to_owned()),
+        })
     }
 
-    pub fn init_default() -> Result<Self> {
+    fn init_default(keyfile: &Path) -> Result<Self> {
+        ensure_interactive(
+            "Choosing a password for your keys",
+            "Run any `litterbox keys` command from a terminal first.",
+        )?;
         eprintln!("Please enter a password to encrypt your keys.");
         let password = Password::new("Password:")
             .with_display_mode(inquire::PasswordDisplayMode::Masked)
             .prompt()?;
-        let s = Self {
-            version: 2,
-            password_hash: hash_password(&password),
-            keys: Vec::new(),
-        };
+        let mut s = Self::new_in_memory(&password, KdfParams::default())?;
+        // Still prompt for it afterwards, like when the file already existed
+        s.password = None;
 
-        s.save_to_file()?;
+        s.save_to(keyfile)?;
         Ok(s)
     }
 
     pub fn load() -> Result<Self> {
-        let keyfile = files::keyfile_path()?;
+        Self::load_from(&files::keyfile_path()?)
+    }
+
+    /// Loads the keys from `keyfile`, creating it when it does not exist yet.
+    pub fn load_from(keyfile: &Path) -> Result<Self> {
         if !keyfile.exists() {
             eprintln!("Keys file does not exist yet. A new one will be created.");
-            return Self::init_default();
+            return Self::init_default(keyfile);
         }
 
-        let contents = files::read_file(keyfile.as_path())?;
-        let keys: Self = ron::from_str(&contents)?;
+        let contents = files::read_file(keyfile)?;
+        let mut keys = Self::parse(&contents, keyfile)?;
+        keys.path = Some(keyfile.to_owned());
+        Ok(keys)
+    }
+
+    /// Parses the contents of the keys file, telling apart files that are
+    /// corrupt from ones that use a format this version cannot handle.
+    fn parse(contents: &str, keyfile: &Path) -> Result<Self> {
+        let header: KeysHeader = ron::from_str(contents).with_context(|| {
+            format!(
+                "Your key file ({}) is corrupt and could not be read.\n\
+                 Restore it from a backup if you have one.",
+                keyfile.display()
+            )
+        })?;
+
+        if header.format_version > FORMAT_VERSION {
+            bail!(
+                "Your key file ({}) uses format version {}, but this version of Litterbox only \
+                 understands up to version {FORMAT_VERSION}.\n\
+                 Please update Litterbox. The file has not been modified.",
+                keyfile.display(),
+                header.format_version,
+            );
+        }
 
-        if keys.version < 2 {
+        if header.format_version < MIN_FORMAT_VERSION {
             bail!(
                 "Your key file ({}) uses an older format that is incompatible with this version.\n\
                  A breaking change in the SSH key encryption library prevents decrypting existing keys.\n\
@@ -161,32 +501,118 @@ impl Keys {
             );
         }
 
+        let keys: Self = ron::from_str(contents).with_context(|| {
+            format!(
+                "Your key file ({}) is corrupt: it has format version {} but its contents do not \
+                 match that format.\n\
+                 Restore it from a backup if you have one.",
+                keyfile.display(),
+                header.format_version,
+            )
+        })?;
+
+        let corrupt = |reason| CorruptKeyfile {
+            path: keyfile.to_owned(),
+            reason,
+        };
+
+        // Files written before checksums were introduced do not have one
+        if let Some(checksum) = &keys.checksum
+            && *checksum != keys.compute_checksum()?
+        {
+            return Err(corrupt("its checksum does not match its contents").into());
+        }
+
+        // Checked before anyone gets asked for the password it would verify
+        if !is_valid_password_hash(&keys.password_hash) {
+            return Err(corrupt("its password hash is not a valid argon2 hash").into());
+        }
+
+        // Checked up front since hashing and encrypting with them would panic
+        let mut all_kdf = std::iter::once(&keys.kdf).chain(keys.secrets.iter().map(|s| &s.kdf));
+        if all_kdf.any(|kdf| kdf.validate().is_err()) {
+            return Err(corrupt("its key derivation parameters are out of range").into());
+        }
+
         Ok(keys)
     }
 
-    pub fn print_list(&self) {
-        let table_rows: Vec<KeyTableRow> = self.keys.iter().map(|c| c.into()).collect();
+    /// Loads the keys file without creating it when it does not exist yet.
+    /// This lets read-only operations work before a password has been chosen.
+    pub fn load_if_exists() -> Result<Option<Self>> {
+        if !files::keyfile_path()?.exists() {
+            debug!("Keys file does not exist yet, nothing to load.");
+            return Ok(None);
+        }
+
+        Self::load().map(Some)
+    }
+
+    /// Prints the keys (and names of the secrets), or only the keys that are
+    /// not attached to any Litterbox when `unused_only` is set
+    pub fn print_list(&self, unused_only: bool) {
+        let table_rows: Vec<KeyTableRow> = self
+            .keys
+            .iter()
+            .filter(|key| !unused_only || key.is_unused())
+            .map(|c| c.into())
+            .collect();
+
+        if unused_only {
+            if table_rows.is_empty() {
+                eprintln!("All keys are attached to at least one Litterbox.");
+            } else {
+                println!("{}", Table::new(table_rows));
+            }
+            return;
+        }
+
         let table = Table::new(table_rows);
 
         println!("{table}");
+
+        if !self.secrets.is_empty() {
+            let names: Vec<&str> = self.secrets.iter().map(|s| s.name.as_str()).collect();
+            println!("Secrets: {}", names.join(", "));
+        }
     }
 
-    pub fn change_password(&mut self) -> Result<()> {
+    pub fn kdf(&self) -> KdfParams {
+        self.kdf
+    }
+
+    /// Changes the password and re-encrypts everything with it using the
+    /// given cost parameters (pass `kdf()` to keep the current ones).
+    pub fn change_password(&mut self, kdf: KdfParams) -> Result<()> {
+        kdf.validate()?;
+
         let old_password = self.prompt_password()?;
         let new_password = Password::new("New password:")
             .with_display_mode(inquire::PasswordDisplayMode::Masked)
             .prompt()?;
 
         for key in &mut self.keys {
-            key.change_password(&old_password, &new_password);
+            key.change_password(&old_password, &new_password, &kdf);
         }
+        for secret in &mut self.secrets {
+            secret.change_password(&old_password, &new_password, &kdf)?;
+        }
+        self.kdf = kdf;
 
-        self.password_hash = hash_password(&new_password);
+        self.password_hash = hash_password(&new_password, &self.kdf);
         self.save_to_file()?;
         Ok(())
     }
 
     fn prompt_password(&self) -> Result<String> {
+        if let Some(password) = &self.password {
+            return Ok(password.clone());
+        }
+
+        ensure_interactive(
+            "Entering the password for your keys",
+            "Run this command from a terminal.",
+        )?;
         eprintln!("Please enter the password you chose to encrypt your keys.");
 
         loop {
@@ -216,12 +642,37 @@ impl Keys {
             bail!("Key \"{key_name}\" already exists.");
         }
 
-        self.add(key_name, &generate_private_key())
+        self.add(key_name, &generate_private_key())?;
+        eprintln!(
+            "Key \"{key_name}\" has been generated with fingerprint {}",
+            self.fingerprint(key_name)?
+        );
+
+        Ok(())
+    }
+
+    /// The SHA-256 fingerprint of a key. Keys added by older versions have
+    /// to be decrypted for it since their public key is not stored.
+    pub fn fingerprint(&self, key_name: &str) -> Result<String> {
+        let key = self
+            .key(key_name)
+            .ok_or_else(|| anyhow!("Key \"{key_name}\" does not exist"))?;
+
+        let public_key = match &key.public_key {
+            Some(public_key) => PublicKey::from_openssh(public_key)
+                .with_context(|| format!("Key \"{key_name}\" has an invalid public key"))?,
+            None => key
+                .try_decrypt(&self.prompt_password()?)?
+                .public_key()
+                .clone(),
+        };
+
+        Ok(public_key.fingerprint(HashAlg::Sha256).to_string())
     }
 
     pub fn add(&mut self, key_name: &str, private_key: &PrivateKey) -> Result<()> {
         let password = self.prompt_password()?;
-        let key = Key::new(key_name, &password, private_key);
+        let key = Key::new(key_name, &password, private_key, &self.kdf);
 
         self.keys.push(key);
         self.save_to_file()
@@ -272,8 +723,77 @@ impl Keys {
         }
     }
 
+    /// Lets the user pick which of `litterbox_names` to attach a key to,
+    /// leaving out the ones it is already attached to.
+    pub fn attach_interactive(
+        &mut self,
+        key_name: &str,
+        litterbox_names: Vec<String>,
+    ) -> Result<()> {
+        let key = self
+            .key(key_name)
+            .ok_or_else(|| anyhow!("Key \"{key_name}\" does not exist"))?;
+
+        let candidates = key.unattached(litterbox_names);
+        if candidates.is_empty() {
+            bail!("There are no Litterboxes that \"{key_name}\" is not attached to yet");
+        }
+
+        ensure_interactive(
+            "Choosing the litterboxes to attach to",
+            "Pass the names of the litterboxes to attach the key to.",
+        )?;
+        let to_attach =
+            MultiSelect::new("Select the litterboxes you want to attach:", candidates).prompt()?;
+
+        for litterbox_name in to_attach {
+            self.attach(key_name, &litterbox_name)?;
+        }
+
+        Ok(())
+    }
+
+    /// Attaches a key to all of `litterbox_names` that it is not attached to
+    /// yet, after confirming the list with the user.
+    pub fn attach_all(&mut self, key_name: &str, litterbox_names: Vec<String>) -> Result<()> {
+        let key = self
+            .key(key_name)
+            .ok_or_else(|| anyhow!("Key \"{key_name}\" does not exist"))?;
+
+        let to_attach = key.unattached(litterbox_names);
+        if to_attach.is_empty() {
+            eprintln!("\"{key_name}\" is already attached to every Litterbox.");
+            return Ok(());
+        }
+
+        eprintln!("\"{key_name}\" will be attached to:");
+        for litterbox_name in &to_attach {
+            eprintln!("  {litterbox_name}");
+        }
+
+        if !assume_yes() {
+            ensure_interactive(
+                "Confirming which litterboxes to attach to",
+                "Pass --yes to confirm up front.",
+            )?;
+            let should_attach = Confirm::new("Attach the key to these litterboxes?")
+                .with_default(false)
+                .prompt()?;
+            if !should_attach {
+                eprintln!("Okay, the key won't be attached!");
+                return Ok(());
+            }
+        }
+
+        for litterbox_name in to_attach {
+            self.attach(key_name, &litterbox_name)?;
+        }
+
+        Ok(())
+    }
+
     pub fn detach(&mut self, key_name: &str) -> Result<()> {
-        match self.key_mut(key_name) {
+        match self.key(key_name) {
             Some(key) => {
                 let to_remove = MultiSelect::new(
                     "Select the litterboxes you want to detach:",
@@ -281,6 +801,17 @@ impl Keys {
                 )
                 .prompt()?;
 
+                self.detach_from(key_name, &to_remove)
+            }
+
+            None => bail!("Key \"{key_name}\" does not exist"),
+        }
+    }
+
+    /// Detaches the given Litterboxes from a key without prompting.
+    pub fn detach_from(&mut self, key_name: &str, to_remove: &[String]) -> Result<()> {
+        match self.key_mut(key_name) {
+            Some(key) => {
                 key.attached_litterboxes
                     .retain(|name| !to_remove.contains(name));
 
@@ -302,6 +833,44 @@ impl Keys {
         }
     }
 
+    /// Marks whether a key gets attached to every newly built Litterbox.
+    pub fn set_default(&mut self, key_name: &str, default: bool) -> Result<()> {
+        let key = self
+            .key_mut(key_name)
+            .ok_or_else(|| anyhow!("Key \"{key_name}\" does not exist"))?;
+        key.default = default;
+        self.save_to_file()?;
+
+        if default {
+            eprintln!("\"{key_name}\" will be attached to new Litterboxes.");
+        } else {
+            eprintln!("\"{key_name}\" will no longer be attached to new Litterboxes.");
+        }
+        Ok(())
+    }
+
+    /// Attaches all default keys to a Litterbox that were not attached yet.
+    pub fn attach_defaults(&mut self, lbx_name: &str) -> Result<()> {
+        let mut attached = Vec::new();
+        for key in self.keys.iter_mut().filter(|key| key.default) {
+            if !key.attached_litterboxes.iter().any(|name| name == lbx_name) {
+                key.attached_litterboxes.push(lbx_name.to_owned());
+                attached.push(key.name.clone());
+            }
+        }
+
+        if attached.is_empty() {
+            return Ok(());
+        }
+
+        self.save_to_file()?;
+        eprintln!(
+            "Attached default keys to litterbox \"{lbx_name}\": {}",
+            attached.join(", ")
+        );
+        Ok(())
+    }
+
     fn attached_keys(&self, lbx_name: &str) -> Vec<&Key> {
         self.keys
             .iter()
@@ -322,21 +891,116 @@ impl Keys {
         }
     }
 
-    pub async fn start_ssh_server(&self, lbx_name: &str, password: &str) -> Result<()> {
-        let agent_state = Arc::new(AgentState::default());
-        let agent_path = start_ssh_agent(lbx_name, agent_state.clone()).await?;
-        debug!("agent_path: {:#?}", agent_path);
+    fn secret(&self, secret_name: &str) -> Option<&Secret> {
+        self.secrets
+            .iter()
+            .find(|secret| secret.name == secret_name)
+    }
+
+    pub fn add_secret(&mut self, secret_name: &str) -> Result<()> {
+        if self.secret(secret_name).is_some() {
+            bail!("Secret \"{secret_name}\" already exists.");
+        }
 
-        let stream = tokio::net::UnixStream::connect(&agent_path)
+        let password = self.prompt_password()?;
+        let value = Password::new("Secret value:")
+            .with_display_mode(inquire::PasswordDisplayMode::Masked)
+            .prompt()?;
+
+        self.secrets
+            .push(Secret::new(secret_name, &password, &value, &self.kdf));
+        self.save_to_file()?;
+
+        eprintln!("Secret \"{secret_name}\" has been added.");
+        Ok(())
+    }
+
+    pub fn delete_secret(&mut self, secret_name: &str) -> Result<()> {
+        if self.secret(secret_name).is_none() {
+            bail!("Secret \"{secret_name}\" does not exist");
+        }
+
+        self.secrets.retain(|secret| secret.name != secret_name);
+        self.save_to_file()?;
+
+        eprintln!("Deleted secret \"{secret_name}\"");
+        Ok(())
+    }
+
+    /// Prompts for the keys password and returns the decrypted secret
+    pub fn reveal_secret(&self, secret_name: &str) -> Result<String> {
+        let secret = self
+            .secret(secret_name)
+            .ok_or_else(|| anyhow!("Secret \"{secret_name}\" does not exist"))?;
+
+        let password = self.prompt_password()?;
+        secret.decrypt(&password)
+    }
+
+    pub async fn start_ssh_server(&self, lbx_name: &str, password: &str) -> Result<AgentHandle> {
+        let settings = LitterboxSettings::load(lbx_name)?;
+        let require_confirmation = settings.as_ref().is_none_or(|s| s.require_confirmation);
+        let audit_log = if settings.as_ref().is_some_and(|s| s.audit_log) {
+            Some(files::audit_log_path(lbx_name)?)
+        } else {
+            None
+        };
+        let auto_approve_signs = settings
+            .as_ref()
+            .filter(|s| s.auto_approve_signs)
+            .map(|s| SignAutoApproval::new(s.auto_approve_signs_per_minute));
+        let policy = settings.map(|s| s.key_policy).unwrap_or_default();
+
+        // The agent will sign with any key it holds, so keys that may not sign
+        // are withheld entirely.
+        let keys: Vec<&Key> = self
+            .attached_keys(lbx_name)
+            .into_iter()
+            .filter(|key| {
+                let may_sign = policy.allows(&key.name, UserRequest::Sign);
+                if !may_sign {
+                    log::info!(
+                        "Key policy does not allow signing, not registering: {}",
+                        key.name
+                    );
+                }
+                may_sign
+            })
+            .collect();
+        let decrypted: Vec<(&Key, PrivateKey)> = keys
+            .iter()
+            .map(|key| (*key, key.decrypt(password)))
+            .collect();
+
+        let agent_state = Arc::new(AgentState {
+            audit_log,
+            auto_approve_signs,
+            allowed_requests: policy.allowed_requests(keys.iter().map(|key| key.name.as_str())),
+            signing_keys: decrypted
+                .iter()
+                .map(|(key, private)| (private.public_key().clone(), key.name.clone()))
+                .collect(),
+            ..Default::default()
+        });
+        let agent = start_ssh_agent(lbx_name, agent_state.clone()).await?;
+        debug!("agent_path: {:#?}", agent.path());
+
+        let stream = tokio::net::UnixStream::connect(agent.path())
             .await
             .context("Failed to connect to SSH agent socket")?;
         let mut client = russh::keys::agent::client::AgentClient::connect(stream);
 
+        // russh neither sends nor keeps key comments, so clients only see the
+        // fingerprints. Logging them along with the names makes it possible to
+        // tell which key `ssh -v` or a server is talking about.
         debug!("Registering keys to SSH agent.");
-        for key in self.attached_keys(lbx_name) {
-            log::info!("Registering key into agent: {}", key.name);
+        for (key, decrypted) in decrypted {
+            debug!(
+                "Registering key into agent: {} ({})",
+                key.name,
+                decrypted.public_key().fingerprint(HashAlg::Sha256)
+            );
 
-            let decrypted = key.decrypt(password);
             client
                 .add_identity(&decrypted, &[])
                 .await
@@ -344,9 +1008,13 @@ impl Keys {
         }
 
         // Ensure the agent will now start prompting for authorization
-        agent_state.locked.store(true, Ordering::SeqCst);
+        if require_confirmation {
+            agent_state.locked.store(true, Ordering::SeqCst);
+        } else {
+            log::warn!("Confirmations are disabled, all agent requests will be approved.");
+        }
 
-        Ok(())
+        Ok(agent)
     }
 
     pub fn print(&self, key_name: &str, private: bool) -> Result<()> {
@@ -362,12 +1030,30 @@ impl Keys {
                 };
 
                 println!("{}", output);
+                eprintln!(
+                    "Fingerprint: {}",
+                    decrypted.public_key().fingerprint(HashAlg::Sha256)
+                );
                 Ok(())
             }
             None => bail!("Key \"{key_name}\" does not exist"),
         }
     }
 
+    /// Checks that a key can be decrypted without using it for anything
+    pub fn verify(&self, key_name: &str) -> Result<()> {
+        let key = self
+            .key(key_name)
+            .ok_or_else(|| anyhow!("Key \"{key_name}\" does not exist"))?;
+
+        let keys_password = self.prompt_password()?;
+        let decrypted = key.try_decrypt(&keys_password)?;
+
+        eprintln!("Key \"{key_name}\" was decrypted successfully.");
+        println!("{}", decrypted.public_key().fingerprint(HashAlg::Sha256));
+        Ok(())
+    }
+
     pub fn export(&self, key_name: &str, path: &Path) -> Result<()> {
         // TODO: just let self.key return the correct error to begin with
         let key = self
@@ -385,7 +1071,14 @@ impl Keys {
         Ok(())
     }
 
-    pub fn import_key(&mut self, key_name: &str, file_path: PathBuf) -> Result<()> {
+    /// Imports a private key from a file. When `expected` is given, the key
+    /// must be its private half.
+    pub fn import_key(
+        &mut self,
+        key_name: &str,
+        file_path: PathBuf,
+        expected: Option<&PublicKey>,
+    ) -> Result<()> {
         if self.key(key_name).is_some() {
             bail!("Key \"{key_name}\" already exists. Please select a different name.");
         }
@@ -423,6 +1116,15 @@ impl Keys {
             }
         };
 
+        if let Some(expected) = expected
+            && private_key.public_key().key_data() != expected.key_data()
+        {
+            bail!(
+                "The key in {file_path:?} is not the private key of {}",
+                expected.fingerprint(HashAlg::Sha256)
+            );
+        }
+
         self.add(key_name, &private_key)?;
         eprintln!("Key \"{key_name}\" has been imported.");
 
@@ -430,6 +1132,26 @@ impl Keys {
     }
 }
 
+/// Finds the private key file for a public key by looking for a matching
+/// `.pub` file in `dir` (normally `~/.ssh`).
+pub fn find_private_key_file(dir: &Path, public_key: &PublicKey) -> Option<PathBuf> {
+    let entries = std::fs::read_dir(dir).ok()?;
+
+    entries.flatten().find_map(|entry| {
+        let path = entry.path();
+        if path.extension().is_none_or(|ext| ext != "pub") {
+            return None;
+        }
+
+        let contents = std::fs::read_to_string(&path).ok()?;
+        let candidate = PublicKey::from_openssh(contents.trim()).ok()?;
+        let private_path = path.with_extension("");
+
+        (candidate.key_data() == public_key.key_data() && private_path.exists())
+            .then_some(private_path)
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -437,7 +1159,7 @@ mod tests {
     #[test]
     fn can_hash_and_verify_password() {
         let password = "some_random_pass";
-        let hash = hash_password(password);
+        let hash = hash_password(password, &KdfParams::default());
         assert_ne!(password, &hash);
 
         assert!(check_password(password, &hash));
@@ -451,13 +1173,193 @@ mod tests {
 
         let encrypted_key = Key {
             name: String::new(),
-            encrypted_key: Key::encrypt(&original_key, password),
+            encrypted_key: Key::encrypt(&original_key, password, &KdfParams::default()),
             attached_litterboxes: Vec::new(),
+            default: false,
+            public_key: None,
         };
         let decrypted_key = encrypted_key.decrypt(password);
         assert_eq!(decrypted_key, original_key);
     }
 
+    #[test]
+    fn lists_litterboxes_a_key_is_not_attached_to() {
+        let key = Key {
+            name: "deploy".to_owned(),
+            encrypted_key: Vec::new(),
+            attached_litterboxes: vec!["web".to_owned()],
+            default: false,
+            public_key: None,
+        };
+
+        let names = ["web", "api", "db"].map(str::to_owned).to_vec();
+        assert_eq!(key.unattached(names), ["api", "db"]);
+        assert!(!key.is_unused());
+
+        let unused = Key {
+            attached_litterboxes: Vec::new(),
+            ..key
+        };
+        assert!(unused.is_unused());
+    }
+
+    #[test]
+    fn can_encrypt_and_decrypt_secret() {
+        let mut secret = Secret::new("user", "SomePassword", "hunter2", &KdfParams::default());
+        assert_ne!(secret.ciphertext, b"hunter2");
+        assert_eq!(secret.decrypt("SomePassword").unwrap(), "hunter2");
+        assert!(secret.decrypt("WrongPassword").is_err());
+
+        secret
+            .change_password("SomePassword", "OtherPassword", &KdfParams::default())
+            .unwrap();
+        assert_eq!(secret.decrypt("OtherPassword").unwrap(), "hunter2");
+    }
+
+    #[test]
+    fn detects_corrupt_and_unsupported_key_files() {
+        let path = Path::new("keys.ron");
+        let mut keys = Keys {
+            format_version: FORMAT_VERSION,
+            password_hash: hash_password("SomePassword", &KdfParams::default()),
+            keys: Vec::new(),
+            secrets: vec![Secret::new(
+                "user",
+                "SomePassword",
+                "hunter2",
+                &KdfParams::default(),
+            )],
+            kdf: KdfParams::default(),
+            checksum: None,
+            path: None,
+            password: None,
+        };
+        keys.checksum = Some(keys.compute_checksum().unwrap());
+        let contents = ron::ser::to_string(&keys).unwrap();
+
+        assert!(Keys::parse(&contents, path).is_ok());
+
+        let tampered = contents.replace("user", "root");
+        let err = Keys::parse(&tampered, path).unwrap_err();
+        assert!(err.is::<CorruptKeyfile>());
+        assert!(err.to_string().contains("checksum"), "{err}");
+
+        keys.password_hash = "not-a-hash".to_owned();
+        keys.checksum = Some(keys.compute_checksum().unwrap());
+        let bad_hash = ron::ser::to_string(&keys).unwrap();
+        let err = Keys::parse(&bad_hash, path).unwrap_err();
+        assert!(err.is::<CorruptKeyfile>());
+        assert!(err.to_string().contains("password hash"), "{err}");
+        assert!(!check_password("SomePassword", "not-a-hash"));
+
+        let truncated = &contents[..contents.len() / 2];
+        let err = Keys::parse(truncated, path).unwrap_err().to_string();
+        assert!(err.contains("corrupt"), "{err}");
+
+        let newer = contents.replace(
+            &format!("format_version:{FORMAT_VERSION}"),
+            &format!("format_version:{}", FORMAT_VERSION + 1),
+        );
+        let err = Keys::parse(&newer, path).unwrap_err().to_string();
+        assert!(err.contains("has not been modified"), "{err}");
+
+        let legacy = "(version:1,password_hash:\"\",keys:[])";
+        let err = Keys::parse(legacy, path).unwrap_err().to_string();
+        assert!(err.contains("older format"), "{err}");
+    }
+
+    #[test]
+    fn loads_key_files_from_before_the_kdf_params() {
+        // Written by the version that introduced checksums
+        const KEYFILE: &str = r#"(format_version:3,password_hash:"$argon2id$v=19$m=19456,t=2,p=1$p52Go1RKXGapeX+aiWwuoQ$CyaM1tX2ihI8D/xWGOiNBXDJMaeyDyJl2RNhRJVbaME",keys:[(name:"work",encrypted_key:[48,129,210,48,110,6,9,42,134,72,134,247,13,1,5,13,48,97,48,64,6,9,42,134,72,134,247,13,1,5,12,48,51,4,32,255,64,105,62,117,190,76,230,231,119,120,30,232,58,0,244,0,86,186,153,237,85,118,64,235,85,206,100,125,42,52,38,2,1,10,48,12,6,8,42,134,72,134,247,13,2,9,5,0,48,29,6,9,96,134,72,1,101,3,4,1,42,4,16,119,228,249,242,56,239,238,29,47,86,66,23,222,158,121,27,4,96,92,28,203,107,188,55,23,213,79,145,129,60,99,219,64,198,218,103,11,14,189,197,28,171,102,168,145,56,238,148,76,225,247,87,202,123,78,175,233,239,162,254,101,122,17,245,226,130,255,238,2,229,43,162,90,3,1,17,81,82,4,102,146,19,98,89,15,36,255,78,222,174,113,245,58,151,13,136,111,30,69,80,101,145,233,190,73,254,8,232,93,215,6,237,5,83],attached_litterboxes:["web"])],secrets:[(name:"user",salt:[197,87,187,34,230,101,202,58,147,205,126,203,84,32,173,39],nonce:[124,203,89,214,224,196,66,157,56,168,197,18],ciphertext:[121,176,103,191,123,144,121,112,167,242,237,26,127,225,217,177,72,116,138,133,21,59,211])],checksum:Some("1789d73c6f7181ae055f313deb55dfa357f3f4425cf3bb798accb8a2a12e7262"))"#;
+
+        let mut keys = Keys::parse(KEYFILE, Path::new("keys.ron")).unwrap();
+        assert_eq!(keys.kdf, KdfParams::default());
+        assert!(check_password("SomePassword", &keys.password_hash));
+        assert_eq!(keys.secrets[0].decrypt("SomePassword").unwrap(), "hunter2");
+        assert!(keys.keys[0].try_decrypt("SomePassword").is_ok());
+
+        // Saving it again without changing anything keeps the same checksum
+        let resaved = ron::ser::to_string(&keys).unwrap();
+        assert!(!resaved.contains("kdf"));
+        assert!(Keys::parse(&resaved, Path::new("keys.ron")).is_ok());
+
+        // Custom cost parameters are written out and covered by the checksum
+        keys.kdf.pbkdf2_rounds = 1000;
+        keys.checksum = Some(keys.compute_checksum().unwrap());
+        let custom = ron::ser::to_string(&keys).unwrap();
+        assert!(custom.contains("kdf"));
+        assert!(Keys::parse(&custom, Path::new("keys.ron")).is_ok());
+
+        keys.kdf.parallelism = 0;
+        keys.checksum = None;
+        let invalid = ron::ser::to_string(&keys).unwrap();
+        let err = Keys::parse(&invalid, Path::new("keys.ron")).unwrap_err();
+        assert!(err.is::<CorruptKeyfile>());
+        assert!(err.to_string().contains("key derivation"), "{err}");
+    }
+
+    #[test]
+    fn decrypting_with_wrong_password_fails() {
+        let key = Key::new(
+            "test",
+            "SomePassword",
+            &generate_private_key(),
+            &KdfParams::default(),
+        );
+
+        assert!(key.try_decrypt("SomePassword").is_ok());
+        assert!(key.try_decrypt("WrongPassword").is_err());
+    }
+
+    #[test]
+    fn stronger_kdf_params_are_used_and_recorded() {
+        let kdf = KdfParams {
+            memory_kib: 8 * 1024,
+            iterations: 3,
+            parallelism: 2,
+            pbkdf2_rounds: 1000,
+        };
+        kdf.validate().unwrap();
+
+        let hash = hash_password("SomePassword", &kdf);
+        assert!(hash.contains("m=8192,t=3,p=2"), "{hash}");
+        assert!(check_password("SomePassword", &hash));
+
+        let secret = Secret::new("user", "SomePassword", "hunter2", &kdf);
+        assert_eq!(secret.kdf, kdf);
+        assert_eq!(secret.decrypt("SomePassword").unwrap(), "hunter2");
+
+        let key = Key::new("test", "SomePassword", &generate_private_key(), &kdf);
+        assert!(key.try_decrypt("SomePassword").is_ok());
+
+        let invalid = KdfParams {
+            memory_kib: 1,
+            ..kdf
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn decrypted_keys_are_named_by_their_comment() {
+        let key = Key::new(
+            "work",
+            "SomePassword",
+            &generate_private_key(),
+            &KdfParams::default(),
+        );
+
+        let decrypted = key.try_decrypt("SomePassword").unwrap();
+        assert_eq!(decrypted.comment(), "work");
+        assert!(
+            decrypted
+                .public_key()
+                .to_openssh()
+                .unwrap()
+                .ends_with(" work")
+        );
+    }
+
     #[test]
     fn export_import_round_trip() {
         let key = generate_private_key();
@@ -470,4 +1372,109 @@ mod tests {
             imported.public_key().to_openssh().unwrap()
         );
     }
+
+    #[test]
+    fn finds_private_key_files_by_their_public_key() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let key = generate_private_key();
+        let other = generate_private_key();
+
+        let write_key = |name: &str, key: &PrivateKey| {
+            let public = key.public_key().to_openssh().unwrap();
+            std::fs::write(dir.path().join(format!("{name}.pub")), public).unwrap();
+            std::fs::write(dir.path().join(name), key_to_openssh(key).unwrap()).unwrap();
+        };
+        write_key("id_other", &other);
+        write_key("id_ed25519", &key);
+        std::fs::write(dir.path().join("known_hosts"), "").unwrap();
+
+        assert_eq!(
+            find_private_key_file(dir.path(), key.public_key()),
+            Some(dir.path().join("id_ed25519"))
+        );
+
+        std::fs::remove_file(dir.path().join("id_ed25519")).unwrap();
+        assert_eq!(find_private_key_file(dir.path(), key.public_key()), None);
+
+        let mut keys = Keys::new_in_memory("SomePassword", KdfParams::default()).unwrap();
+        let other_path = dir.path().join("id_other");
+        assert!(
+            keys.import_key("work", other_path.clone(), Some(key.public_key()))
+                .is_err()
+        );
+        keys.import_key("work", other_path, Some(other.public_key()))
+            .unwrap();
+    }
+
+    #[test]
+    fn fingerprints_keys_with_and_without_a_stored_public_key() {
+        let mut keys = Keys::new_in_memory("SomePassword", KdfParams::default()).unwrap();
+        keys.generate("work").unwrap();
+
+        let expected = keys.keys[0]
+            .decrypt("SomePassword")
+            .public_key()
+            .fingerprint(HashAlg::Sha256)
+            .to_string();
+        assert!(keys.keys[0].public_key.is_some());
+        assert_eq!(keys.fingerprint("work").unwrap(), expected);
+
+        // Like a key added before public keys were stored
+        keys.keys[0].public_key = None;
+        assert_eq!(keys.fingerprint("work").unwrap(), expected);
+        assert!(keys.fingerprint("missing").is_err());
+    }
+
+    #[test]
+    fn in_memory_keys_round_trip_through_a_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("keys.ron");
+
+        let mut keys = Keys::new_in_memory("SomePassword", KdfParams::default()).unwrap();
+        keys.generate("work").unwrap();
+        keys.attach("work", "dev").unwrap();
+        keys.attach("work", "scratch").unwrap();
+        assert!(!path.exists(), "in-memory keys should not be saved");
+
+        keys.save_to(&path).unwrap();
+        let loaded = Keys::load_from(&path).unwrap();
+        assert!(check_password("SomePassword", &loaded.password_hash));
+        assert_eq!(
+            loaded.key("work").unwrap().attached_litterboxes,
+            ["dev", "scratch"]
+        );
+        assert!(
+            loaded
+                .key("work")
+                .unwrap()
+                .try_decrypt("SomePassword")
+                .is_ok()
+        );
+
+        keys.detach_from("work", &["dev".to_owned()]).unwrap();
+        let loaded = Keys::load_from(&path).unwrap();
+        assert_eq!(
+            loaded.key("work").unwrap().attached_litterboxes,
+            ["scratch"]
+        );
+        assert!(!loaded.has_attached_keys("dev"));
+
+        keys.generate("personal").unwrap();
+        keys.set_default("personal", true).unwrap();
+        keys.attach_defaults("scratch").unwrap();
+        keys.attach_defaults("scratch").unwrap();
+        let loaded = Keys::load_from(&path).unwrap();
+        assert!(loaded.key("personal").unwrap().default);
+        assert_eq!(loaded.attached_keys("scratch").len(), 2);
+        assert_eq!(
+            loaded.key("personal").unwrap().attached_litterboxes,
+            ["scratch"]
+        );
+        assert!(!loaded.key("work").unwrap().default);
+
+        keys.delete("work").unwrap();
+        let loaded = Keys::load_from(&path).unwrap();
+        assert!(loaded.key("work").is_none());
+        assert!(keys.delete("work").is_err());
+    }
 }
@@ -3,20 +3,42 @@ use std::env::VarError;
 
 mod agent;
 mod commands;
+mod config;
 mod daemon;
+mod definition;
 mod devices;
 mod env;
 mod files;
 mod keys;
+mod output;
 mod podman;
+mod selinux;
 mod settings;
 mod template;
+mod update;
 mod utils;
 
 /// Simple sandbox utility aimed at software development
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
+    /// Automatically answer yes to confirmation prompts
+    #[arg(long, short, global = true)]
+    yes: bool,
+
+    /// Report errors as a single JSON object on STDERR
+    #[arg(long, global = true)]
+    json_errors: bool,
+
+    /// Log more details (can be repeated), unless RUST_LOG is set
+    #[arg(long, short, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Print how long podman invocations and key handling take (the same as
+    /// setting LITTERBOX_TRACE=1)
+    #[arg(long, global = true)]
+    trace: bool,
+
     #[command(subcommand)]
     command: crate::commands::Command,
 }
@@ -27,15 +49,51 @@ fn main() -> anyhow::Result<()> {
     // Configure default log level for debug and release builds.
     if std::env::var("RUST_LOG").is_err_and(|e| e == VarError::NotPresent) {
         // SAFETY: No other threads are reading or writing to env variables.
+        // The daemon inherits the level since it gets passed on through the
+        // environment.
         unsafe {
-            #[cfg(debug_assertions)]
-            std::env::set_var("RUST_LOG", "debug");
+            match args.verbose {
+                0 => {
+                    #[cfg(debug_assertions)]
+                    std::env::set_var("RUST_LOG", "debug");
 
-            #[cfg(not(debug_assertions))]
-            std::env::set_var("RUST_LOG", "info");
+                    #[cfg(not(debug_assertions))]
+                    std::env::set_var("RUST_LOG", "info");
+                }
+                1 => std::env::set_var("RUST_LOG", "debug"),
+                _ => std::env::set_var("RUST_LOG", "trace"),
+            }
         }
     }
 
+    if args.trace {
+        // SAFETY: No other threads are reading or writing to env variables
+        // yet. This passes the flag on to the daemon.
+        unsafe { std::env::set_var(env::TRACE_VAR, "1") };
+    }
+
     env_logger::init();
-    args.command.run()
+    utils::init_span_timing();
+    utils::set_assume_yes(args.yes);
+
+    // The agent waits for the output of `confirm`, so it must never be held
+    // up by a network request
+    let notify_updates = !matches!(
+        args.command,
+        commands::Command::UpdateCheck(_)
+            | commands::Command::Confirm(_)
+            | commands::Command::Daemon(_)
+    );
+    match args.command.run() {
+        Err(e) if args.json_errors => {
+            eprintln!("{}", utils::error_to_json(&e));
+            std::process::exit(1);
+        }
+        result => {
+            if notify_updates {
+                update::notify_if_enabled();
+            }
+            result
+        }
+    }
 }
@@ -0,0 +1,87 @@
+//! JSON output for scripts. All of it is wrapped in a versioned envelope,
+//! `{ "schema": 1, "data": ... }`, and built from the structs below rather
+//! than from podman's JSON, so that the field names are a stable contract
+//! that only changes along with `SCHEMA_VERSION`.
+
+use serde::Serialize;
+
+/// Bumped whenever a field of the JSON output is renamed, removed or changes
+/// meaning. Adding fields does not bump it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    schema: u32,
+    data: &'a T,
+}
+
+/// Wraps output data in the versioned envelope
+pub fn to_json<T: Serialize>(data: &T) -> serde_json::Value {
+    serde_json::to_value(Envelope {
+        schema: SCHEMA_VERSION,
+        data,
+    })
+    .expect("Output should always serialise")
+}
+
+/// A Litterbox as printed by `list --json`
+#[derive(Debug, Serialize)]
+pub struct LitterboxEntry {
+    pub name: String,
+    pub container_id: String,
+    pub container_names: Vec<String>,
+    pub image: String,
+    pub image_id: String,
+    /// The podman state of the container, e.g. "running" or "exited"
+    pub state: &'static str,
+    /// Creation time of the container as a UNIX timestamp
+    pub created: i64,
+    /// Disk space used by the image and home directory, only with `--size`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_bytes: Option<u64>,
+}
+
+/// An error as printed by `--json-errors`
+#[derive(Debug, Serialize)]
+pub struct ErrorReport {
+    /// Machine readable kind of error, e.g. "socket_in_use"
+    pub error: &'static str,
+    pub message: String,
+    /// The causes behind the error, joined with ": "
+    pub detail: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_output_in_a_versioned_envelope() {
+        let entry = LitterboxEntry {
+            name: "dev".to_owned(),
+            container_id: "abc".to_owned(),
+            container_names: vec!["lbx-dev".to_owned()],
+            image: "localhost/lbx-dev:latest".to_owned(),
+            image_id: "def".to_owned(),
+            state: "running",
+            created: 1700000000,
+            size_bytes: None,
+        };
+
+        assert_eq!(
+            to_json(&[entry]),
+            serde_json::json!({
+                "schema": 1,
+                "data": [{
+                    "name": "dev",
+                    "container_id": "abc",
+                    "container_names": ["lbx-dev"],
+                    "image": "localhost/lbx-dev:latest",
+                    "image_id": "def",
+                    "state": "running",
+                    "created": 1700000000,
+                }],
+            })
+        );
+    }
+}
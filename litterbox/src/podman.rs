@@ -1,30 +1,72 @@
 use anyhow::{Context, Result, anyhow, bail, ensure};
-use inquire::Confirm;
+use inquire::{Confirm, Select};
 use log::info;
-use log::{debug, warn};
-use nix::unistd::{getgid, getuid};
+use log::{debug, error, warn};
+use nix::{
+    sys::signal::{Signal, kill},
+    unistd::{Pid, getgid, getuid},
+};
 use serde::Deserialize;
 use std::{
-    ffi::OsString,
+    collections::HashMap,
+    fmt::Display,
     fs,
-    io::ErrorKind,
+    io::{ErrorKind, IsTerminal, Write},
     path::{Path, PathBuf},
-    process::{Child, Command, Stdio},
+    process::{Child, Command, ExitStatus, Output, Stdio},
+    sync::mpsc::{self, RecvTimeoutError},
 };
 
 use crate::{
-    env,
+    daemon, devices, env,
     files::{self, SshSockFile},
     keys::Keys,
-    settings::LitterboxSettings,
-    utils::{extract_stdout, podman_name, trace_arguments},
+    selinux,
+    settings::{AttachedDevice, DRI_DEVICE, DXG_DEVICE, KFD_DEVICE, KVM_DEVICE, LitterboxSettings},
+    utils::{
+        assume_yes, closest_match, ensure_interactive, extract_stdout, format_age, glob_matches,
+        podman_name, trace_arguments,
+    },
 };
 use crate::{
     files::{dockerfile_path, write_file},
-    template::Template,
+    template::{all_templates, template_contents},
 };
 
-const LBX_USER: &str = "user";
+pub const LBX_USER: &str = "user";
+
+/// Label namespace reserved for the labels that Litterbox itself manages
+const LBX_LABEL_NAMESPACE: &str = "work.litterbox.";
+
+/// Parses a user supplied `KEY=VALUE` label and rejects anything that would
+/// clash with the labels that Litterbox manages itself.
+pub fn parse_user_label(label: &str) -> Result<String> {
+    let (key, _value) = label
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Label \"{label}\" should be in the form KEY=VALUE"))?;
+
+    ensure!(!key.is_empty(), "Label \"{label}\" has an empty key");
+    ensure!(
+        !key.starts_with(LBX_LABEL_NAMESPACE),
+        "Label \"{label}\" uses the reserved \"{LBX_LABEL_NAMESPACE}*\" namespace"
+    );
+
+    Ok(label.to_owned())
+}
+
+/// Parses a user supplied `KEY=VALUE` OCI annotation
+pub fn parse_annotation(annotation: &str) -> Result<(String, String)> {
+    let (key, value) = annotation
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Annotation \"{annotation}\" should be in the form KEY=VALUE"))?;
+
+    ensure!(
+        !key.is_empty(),
+        "Annotation \"{annotation}\" has an empty key"
+    );
+
+    Ok((key.to_owned(), value.to_owned()))
+}
 
 /// Represents the GPU device configuration for the container
 enum GpuDevice {
@@ -37,11 +79,11 @@ enum GpuDevice {
 impl GpuDevice {
     /// Detects the available GPU device based on what exists on the system
     fn try_detect() -> Option<Self> {
-        if Path::new("/dev/dri").exists() {
-            debug!("/dev/dri available");
+        if Path::new(DRI_DEVICE).exists() {
+            debug!("{DRI_DEVICE} available");
             Some(Self::Dri)
-        } else if Path::new("/dev/dxg").exists() {
-            debug!("/dev/dxg available (WSL)");
+        } else if Path::new(DXG_DEVICE).exists() {
+            debug!("{DXG_DEVICE} available (WSL)");
             Some(Self::Dxg)
         } else {
             None
@@ -50,16 +92,15 @@ impl GpuDevice {
 
     fn device_path(&self) -> &'static str {
         match self {
-            GpuDevice::Dri => "/dev/dri",
-            GpuDevice::Dxg => "/dev/dxg",
+            GpuDevice::Dri => DRI_DEVICE,
+            GpuDevice::Dxg => DXG_DEVICE,
         }
     }
 
-    fn volume_mount(&self) -> &'static str {
-        match self {
-            GpuDevice::Dri => "/dev/dri:/dev/dri",
-            GpuDevice::Dxg => "/dev/dxg:/dev/dxg",
-        }
+    /// `--device` sets up both the device nodes and the cgroup rules, so the
+    /// device must not also be bind mounted.
+    fn podman_args(&self) -> [&'static str; 2] {
+        ["--device", self.device_path()]
     }
 }
 
@@ -67,6 +108,24 @@ impl GpuDevice {
 pub struct ContainerLabels {
     #[serde(rename = "work.litterbox.name")]
     pub name: String,
+    /// All the other labels, including the ones given at build time
+    #[serde(flatten)]
+    other: HashMap<String, String>,
+}
+
+impl ContainerLabels {
+    /// The labels outside of the Litterbox namespace as `KEY=VALUE`, sorted
+    /// so that they can be passed on to a rebuild
+    pub fn user_labels(&self) -> Vec<String> {
+        let mut labels: Vec<String> = self
+            .other
+            .iter()
+            .filter(|(key, _)| !key.starts_with(LBX_LABEL_NAMESPACE))
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect();
+        labels.sort();
+        labels
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
@@ -85,6 +144,23 @@ pub enum ContainerState {
     Unknown,
 }
 
+impl ContainerState {
+    /// The name podman uses for the state
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContainerState::Created => "created",
+            ContainerState::Initialized => "initialized",
+            ContainerState::Running => "running",
+            ContainerState::Stopped => "stopped",
+            ContainerState::Paused => "paused",
+            ContainerState::Exited => "exited",
+            ContainerState::Removing => "removing",
+            ContainerState::Stopping => "stopping",
+            ContainerState::Unknown => "unknown",
+        }
+    }
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct Container {
     #[serde(rename = "Id")]
@@ -104,24 +180,68 @@ pub struct Container {
 
     #[serde(rename = "State")]
     pub state: ContainerState,
+
+    /// Creation time as a UNIX timestamp
+    #[serde(rename = "Created", default)]
+    pub created: i64,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct Containers(pub Vec<Container>);
 
+impl Containers {
+    /// The container of the Litterbox with this name, if there is one
+    pub fn find_by_name(&self, lbx_name: &str) -> Option<&Container> {
+        self.0.iter().find(|c| c.labels.name == lbx_name)
+    }
+
+    /// The containers that are currently running
+    pub fn running(&self) -> impl Iterator<Item = &Container> {
+        self.0.iter().filter(|c| c.state == ContainerState::Running)
+    }
+
+    /// The names of the Litterboxes, sorted and without duplicates
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.0.iter().map(|c| c.labels.name.clone()).collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Only keeps the Litterboxes whose names match the optional glob pattern
+    pub fn matching(self, pattern: Option<&str>) -> Self {
+        Self(
+            self.0
+                .into_iter()
+                .filter(|c| pattern.is_none_or(|pattern| glob_matches(pattern, &c.labels.name)))
+                .collect(),
+        )
+    }
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct Image {
     #[serde(rename = "Id")]
     pub id: String,
 
-    #[serde(rename = "Names")]
+    #[serde(rename = "Names", default)]
     pub names: Vec<String>,
+
+    /// Creation time as a UNIX timestamp
+    #[serde(rename = "Created", default)]
+    pub created: i64,
+
+    /// Size in bytes
+    #[serde(rename = "Size", default)]
+    pub size: u64,
 }
 
 #[derive(Deserialize, Debug)]
 struct Images(Vec<Image>);
 
-pub fn get_containers() -> Result<Containers> {
+/// Gets all Litterbox containers, optionally narrowed down by additional
+/// podman `--filter` expressions.
+pub fn get_containers(filters: &[String]) -> Result<Containers> {
     let mut cmd = Command::new("podman");
     cmd.args([
         "ps",
@@ -131,8 +251,10 @@ pub fn get_containers() -> Result<Containers> {
         "--filter",
         "label=work.litterbox.name",
     ]);
-    trace_arguments(&cmd);
-    let output = cmd.output().context("Failed to run 'podman' command")?;
+    for filter in filters {
+        cmd.args(["--filter", filter]);
+    }
+    let output = podman_output(cmd)?;
 
     let stdout = extract_stdout(&output)?;
     Ok(serde_json::from_str(stdout)?)
@@ -148,8 +270,7 @@ pub fn get_containers_by_name(lbx_name: &str) -> Result<Containers> {
         "--filter",
         &format!("label=work.litterbox.name={lbx_name}"),
     ]);
-    trace_arguments(&cmd);
-    let output = cmd.output().context("Failed to run podman command")?;
+    let output = podman_output(cmd)?;
 
     Ok(serde_json::from_str(extract_stdout(&output)?)?)
 }
@@ -160,19 +281,115 @@ pub fn get_container(lbx_name: &str) -> Result<Option<Container>> {
     match containers.len() {
         0 => Ok(None),
         1 => Ok(Some(containers.swap_remove(0))),
-        _ => bail!("Multiple containers found for \"{lbx_name}\""),
+        _ => bail!(
+            "Multiple containers found for \"{lbx_name}\". Run `litterbox repair {lbx_name}` to resolve this."
+        ),
     }
 }
 
-pub fn is_container_running(lbx_name: &str) -> Result<bool> {
-    let containers = get_containers_by_name(lbx_name)?.0;
+/// Lets the user choose one of the existing Litterboxes from a menu that can
+/// be filtered by typing.
+pub fn pick_litterbox(action: &str) -> Result<String> {
+    let names = get_containers(&[])?.names();
+    if names.is_empty() {
+        bail!("No Litterboxes have been built yet.");
+    }
+
+    ensure_interactive(
+        "Choosing a Litterbox",
+        "Pass the name of the Litterbox as an argument.",
+    )?;
+    let name = Select::new(
+        &format!("Which Litterbox would you like to {action}?"),
+        names,
+    )
+    .prompt()?;
+    Ok(name)
+}
+
+/// Gets the container for a Litterbox, failing with suggestions of similarly
+/// named Litterboxes when it does not exist.
+pub fn require_container(lbx_name: &str) -> Result<Container> {
+    get_container(lbx_name)?.ok_or_else(|| no_container_error(lbx_name))
+}
+
+pub fn no_container_error(lbx_name: &str) -> anyhow::Error {
+    let names: Vec<String> = match get_containers(&[]) {
+        Ok(containers) => containers.names(),
+        Err(e) => {
+            debug!("Could not list Litterboxes for suggestions: {e:#}");
+            return anyhow!("No Litterbox named '{lbx_name}'.");
+        }
+    };
+
+    if names.is_empty() {
+        return anyhow!("No Litterbox named '{lbx_name}'. No Litterboxes have been built yet.");
+    }
+
+    let mut message = format!("No Litterbox named '{lbx_name}'.");
+    if let Some(suggestion) = closest_match(lbx_name, &names) {
+        message.push_str(&format!(" Did you mean '{suggestion}'?"));
+    }
+    message.push_str(&format!("\nAvailable Litterboxes: {}", names.join(", ")));
+
+    anyhow!(message)
+}
+
+/// Gets all images that belong to a Litterbox, including ones that no
+/// container uses anymore
+fn get_litterbox_images() -> Result<Vec<Image>> {
+    let mut cmd = Command::new("podman");
+    cmd.args([
+        "image",
+        "ls",
+        "--all",
+        "--format",
+        "json",
+        "--filter",
+        "label=work.litterbox.name",
+    ]);
+    let output = podman_output(cmd)?;
+    let Images(images) = serde_json::from_str(extract_stdout(&output)?)?;
+
+    Ok(images)
+}
 
+/// Sizes in bytes of all Litterbox images, keyed by image id
+pub fn get_image_sizes() -> Result<HashMap<String, u64>> {
+    Ok(get_litterbox_images()?
+        .into_iter()
+        .map(|image| (image.id, image.size))
+        .collect())
+}
+
+/// Returns the raw `podman inspect` output for the container of a Litterbox,
+/// optionally formatted with a Go template.
+pub fn inspect_container(lbx_name: &str, format: Option<&str>) -> Result<String> {
+    let container = require_container(lbx_name)?;
+
+    let mut cmd = Command::new("podman");
+    cmd.arg("inspect");
+    if let Some(format) = format {
+        cmd.args(["--format", format]);
+    }
+    cmd.arg(&container.id);
+
+    let output = podman_output(cmd)?;
+    Ok(extract_stdout(&output)?.to_owned())
+}
+
+/// Whether the container of a Litterbox is currently running. Litterboxes
+/// that have not been built are not running.
+pub fn is_running(lbx_name: &str) -> Result<bool> {
+    // `podman ps` already reports the state, which saves the daemon an extra
+    // podman call every time it checks
+    let containers = get_containers_by_name(lbx_name)?;
     Ok(containers
-        .first()
+        .find_by_name(lbx_name)
         .is_some_and(|c| c.state == ContainerState::Running))
 }
 
-pub fn get_image(lbx_name: &str) -> Result<Option<Image>> {
+fn get_images_by_name(lbx_name: &str) -> Result<Vec<Image>> {
     let mut cmd = Command::new("podman");
     cmd.args([
         "image",
@@ -187,35 +404,320 @@ pub fn get_image(lbx_name: &str) -> Result<Option<Image>> {
         // rebuilt.
         "dangling=false",
     ]);
-    trace_arguments(&cmd);
-    let output = cmd.output().context("Failed to run podman command")?;
+    let output = podman_output(cmd)?;
 
     let stdout = extract_stdout(&output)?;
-    let Images(mut images) = serde_json::from_str(stdout)?;
+    let Images(images) = serde_json::from_str(stdout)?;
+    Ok(images)
+}
 
-    match images.len() {
-        0 => Ok(None),
-        1 => Ok(Some(images.swap_remove(0))),
-        _ => bail!("Multiple images found for \"{lbx_name}\""),
+/// Whether the file at `path` was modified after `time`
+fn modified_after(path: &Path, time: std::time::SystemTime) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .is_ok_and(|modified| modified > time)
+}
+
+/// Whether the Dockerfile of a Litterbox was changed after Litterbox last
+/// built the image of its container, meaning that the changes are not in
+/// effect yet
+pub fn image_is_stale(lbx_name: &str, container: &Container) -> Result<bool> {
+    // Builds that come entirely from the cache keep the creation time of the
+    // image, so the image id file that every build writes is used instead.
+    let iid_path = files::iid_path(lbx_name)?;
+    let built_id = fs::read_to_string(&iid_path).unwrap_or_default();
+    if built_id.trim().trim_start_matches("sha256:") != container.image_id {
+        debug!("{iid_path:?} does not name the image of '{lbx_name}', not checking it");
+        return Ok(false);
+    }
+
+    let built = fs::metadata(&iid_path)
+        .and_then(|metadata| metadata.modified())
+        .with_context(|| format!("Could not read metadata of {iid_path:?}"))?;
+    Ok(modified_after(&files::dockerfile_path(lbx_name)?, built))
+}
+
+/// Picks the image to keep out of several with the same name: the one that
+/// the container uses if any, otherwise the newest. Returns it and the others.
+fn split_preferred_image(mut images: Vec<Image>, in_use: Option<&str>) -> (Image, Vec<Image>) {
+    images.sort_by_key(|image| (in_use == Some(image.id.as_str()), image.created));
+    let preferred = images.pop().expect("There should be at least one image");
+
+    (preferred, images)
+}
+
+/// Gets the image of a Litterbox. Should there be several (e.g. after an
+/// interrupted build), the preferred one gets used and the user is offered
+/// to delete the others.
+pub fn get_image(lbx_name: &str) -> Result<Option<Image>> {
+    let mut images = get_images_by_name(lbx_name)?;
+    if images.len() <= 1 {
+        return Ok(images.pop());
+    }
+
+    let in_use = get_container(lbx_name).ok().flatten().map(|c| c.image_id);
+    let (image, extras) = split_preferred_image(images, in_use.as_deref());
+    warn!(
+        "Multiple images found for \"{lbx_name}\". Using {} (created {}).",
+        &image.id[..image.id.len().min(12)],
+        format_age(image.created)
+    );
+
+    let remove_extras = if assume_yes() {
+        true
+    } else if std::io::stdin().is_terminal() {
+        Confirm::new(&format!(
+            "Do you want to delete the {} other image(s)?",
+            extras.len()
+        ))
+        .with_default(true)
+        .prompt()?
+    } else {
+        false
+    };
+
+    if !remove_extras {
+        eprintln!("Run `litterbox repair {lbx_name}` to clean up the other images.");
+        return Ok(Some(image));
+    }
+
+    for extra in &extras {
+        let mut cmd = Command::new("podman");
+        cmd.args(["image", "rm", &extra.id]);
+        cmd.stdout(Stdio::null());
+        let span = trace_arguments(&cmd);
+
+        let removed = cmd
+            .spawn()
+            .context("Failed to run podman command")
+            .and_then(wait_for_podman);
+        drop(span);
+        match removed {
+            Ok(()) => eprintln!("Deleted image {}", &extra.id[..extra.id.len().min(12)]),
+            Err(e) => warn!("Failed to delete image {}: {e:#}", extra.id),
+        }
+    }
+
+    Ok(Some(image))
+}
+
+#[derive(Deserialize, Debug)]
+struct PodmanInfo {
+    host: PodmanHost,
+}
+
+#[derive(Deserialize, Debug)]
+struct PodmanHost {
+    #[serde(rename = "serviceIsRemote", default)]
+    service_is_remote: bool,
+}
+
+/// Litterbox wires host devices, Wayland sockets and the home directory
+/// straight into the container. That only works when podman runs on this
+/// Linux machine rather than in a VM (e.g. `podman machine` on macOS).
+fn ensure_local_podman() -> Result<()> {
+    let mut cmd = Command::new("podman");
+    cmd.args(["info", "--format", "json"]);
+    let output = podman_output(cmd)?;
+
+    let stdout = extract_stdout(&output)?;
+    let info: PodmanInfo = serde_json::from_str(stdout).context("Failed to parse podman info")?;
+    debug!("Podman info: {info:?}");
+
+    if info.host.service_is_remote || !cfg!(target_os = "linux") {
+        bail!(
+            "Podman is running in a virtual machine (e.g. `podman machine`) or on a remote host.\n\
+             Litterbox needs podman to run directly on a Linux host since it passes GPU devices, \
+             the Wayland display and the home directory straight into the container. \
+             These would be missing or broken inside a VM, so no Litterbox was created."
+        );
     }
+
+    Ok(())
 }
 
-pub fn define_litterbox(lbx_name: &str) -> anyhow::Result<()> {
+/// Path for the Dockerfile of a Litterbox that has not been defined yet
+fn new_dockerfile_path(lbx_name: &str) -> Result<PathBuf> {
     let dockerfile = dockerfile_path(lbx_name)?;
 
     if dockerfile.exists() {
         bail!("Dockerfile already exists at {dockerfile:?}");
     }
 
-    let template = Template::select("Choose a template:").prompt()?;
+    Ok(dockerfile)
+}
+
+/// Writes the Dockerfile for a new Litterbox. The user gets prompted to choose
+/// a template if none was provided.
+pub fn define_litterbox(lbx_name: &str, template: Option<&str>) -> anyhow::Result<()> {
+    let dockerfile = new_dockerfile_path(lbx_name)?;
+
+    let template = match template {
+        Some(template) => template.to_owned(),
+        None => {
+            ensure_interactive(
+                "Choosing a template",
+                &format!("Write a Dockerfile to {dockerfile:?} yourself instead."),
+            )?;
+            Select::new("Choose a template:", all_templates()?)
+                .prompt()?
+                .name
+        }
+    };
 
-    write_file(dockerfile.as_path(), template.contents())?;
+    // Validated before writing so that a broken custom template cannot leave
+    // behind a Dockerfile that only fails once it gets built
+    let contents = template_contents(&template)?;
+    write_file(dockerfile.as_path(), &contents)?;
     info!("Default Dockerfile written to {dockerfile:?}");
 
     Ok(())
 }
 
-pub fn build_image(lbx_name: &str) -> Result<()> {
+/// Writes the Dockerfile for a new Litterbox verbatim from STDIN
+pub fn define_litterbox_from_stdin(lbx_name: &str) -> Result<()> {
+    use std::io::Read;
+
+    let dockerfile = new_dockerfile_path(lbx_name)?;
+
+    let mut contents = String::new();
+    std::io::stdin()
+        .read_to_string(&mut contents)
+        .context("Failed to read Dockerfile from STDIN")?;
+    ensure!(
+        !contents.trim().is_empty(),
+        "No Dockerfile was provided on STDIN"
+    );
+
+    write_file(dockerfile.as_path(), &contents)?;
+    info!("Dockerfile written to {dockerfile:?}");
+
+    Ok(())
+}
+
+/// A `STEP n/m: INSTRUCTION` marker from the output of `podman build`
+#[derive(Debug, PartialEq)]
+struct BuildStep<'a> {
+    current: u32,
+    total: u32,
+    instruction: &'a str,
+}
+
+impl<'a> BuildStep<'a> {
+    /// Longest instruction that gets shown, so the progress stays on one line
+    const MAX_INSTRUCTION_LEN: usize = 60;
+
+    /// Multi-stage builds prefix the marker with the stage, e.g.
+    /// `[1/2] STEP 3/5: RUN make`, so the marker is searched for.
+    fn parse(line: &'a str) -> Option<Self> {
+        let (_, step) = line.split_once("STEP ")?;
+        let (counts, instruction) = step.split_once(": ")?;
+        let (current, total) = counts.split_once('/')?;
+
+        Some(Self {
+            current: current.parse().ok()?,
+            total: total.parse().ok()?,
+            instruction: instruction.trim(),
+        })
+    }
+}
+
+impl Display for BuildStep<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}/{}] ", self.current, self.total)?;
+
+        match self
+            .instruction
+            .char_indices()
+            .nth(Self::MAX_INSTRUCTION_LEN)
+        {
+            Some((end, _)) => write!(f, "{}...", &self.instruction[..end]),
+            None => write!(f, "{}", self.instruction),
+        }
+    }
+}
+
+/// Runs `podman build` while condensing its output into a single line that
+/// shows the current step. The full output is only shown if the build fails.
+fn run_build_with_progress(mut cmd: Command) -> Result<()> {
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+    let mut child = cmd.spawn().context("Failed to run podman command")?;
+
+    let streams: [Box<dyn Read + Send>; 2] = [
+        Box::new(child.stdout.take().expect("stdout should be piped")),
+        Box::new(child.stderr.take().expect("stderr should be piped")),
+    ];
+    let (tx, rx) = mpsc::channel();
+    for stream in streams {
+        let tx = tx.clone();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    drop(tx);
+
+    let mut output = Vec::new();
+    let mut stderr = std::io::stderr();
+    for line in rx {
+        if let Some(step) = BuildStep::parse(&line) {
+            // Overwrite the previous step
+            let _ = write!(stderr, "\r\x1b[2K{step}");
+            let _ = stderr.flush();
+        }
+        output.push(line);
+    }
+    eprintln!();
+
+    let res = child.wait().context("Failed to run podman command")?;
+    if !res.success() {
+        for line in &output {
+            eprintln!("{line}");
+        }
+        bail!("Podman command failed");
+    }
+
+    Ok(())
+}
+
+/// Whether a Dockerfile mounts the `user_password` build secret
+fn uses_user_password(dockerfile: &str) -> bool {
+    dockerfile.contains("id=user_password")
+}
+
+/// Whether the Dockerfile of a Litterbox needs a user password. A Dockerfile
+/// that does not exist yet is assumed to, since the templates all do.
+pub fn dockerfile_needs_user_password(lbx_name: &str) -> Result<bool> {
+    let dockerfile_path = files::dockerfile_path(lbx_name)?;
+    if !dockerfile_path.exists() {
+        return Ok(true);
+    }
+
+    Ok(uses_user_password(&files::read_file(&dockerfile_path)?))
+}
+
+/// Builds (or re-uses) the image for a Litterbox and returns its id. When a
+/// user password is provided, it is made available to the Dockerfile as the
+/// `user_password` build secret so that it does not end up in the image history.
+/// The base image falls back to the one pinned in the settings, if any. An
+/// existing image is rebuilt without asking when `force` is set.
+pub fn build_image(
+    lbx_name: &str,
+    labels: &[String],
+    user_password: Option<&str>,
+    base_image: Option<&str>,
+    force: bool,
+) -> Result<String> {
+    // Fail before spending time on a build that could never be used
+    ensure_local_podman()?;
+    env::wayland_socket_path()?;
+
     let image_name = match get_image(lbx_name)? {
         Some(details) => {
             assert!(!details.names.is_empty(), "All images should have a name.");
@@ -224,16 +726,22 @@ pub fn build_image(lbx_name: &str) -> Result<()> {
             }
 
             eprintln!("An image for this Litterbox already exists.");
-            if Confirm::new("Would you like to rebuild the image?")
-                .with_default(true)
-                .prompt()?
-            {
+            let rebuild = force || assume_yes() || {
+                ensure_interactive(
+                    "Deciding whether to rebuild the image",
+                    "Pass --force to rebuild it without asking.",
+                )?;
+                Confirm::new("Would you like to rebuild the image?")
+                    .with_default(true)
+                    .prompt()?
+            };
+            if rebuild {
                 eprintln!("The image will now be rebuilt!");
             } else {
                 eprintln!("The existing image will be re-used!");
 
                 // Exit the whole function since we don't need to do anything more
-                return Ok(());
+                return Ok(details.id);
             }
             details.names[0].clone()
         }
@@ -246,9 +754,27 @@ pub fn build_image(lbx_name: &str) -> Result<()> {
     if !dockerfile_path.exists() {
         info!("{dockerfile_path:?} does not exist.");
         // Ask the user right away for convenience. They can always CTRL + C
-        define_litterbox(lbx_name)?;
+        define_litterbox(lbx_name, None)?;
     }
 
+    let pinned_base_image = LitterboxSettings::load(lbx_name)?.and_then(|s| s.base_image);
+    let base_image = base_image.or(pinned_base_image.as_deref());
+
+    run_image_build(lbx_name, &image_name, labels, user_password, base_image)
+}
+
+/// Builds the image of a Litterbox from its Dockerfile under `image_name`
+/// and returns its id. The base image is passed on as the `BASE_IMAGE`
+/// build-arg, leaving the Dockerfile's default in place when there is none.
+fn run_image_build(
+    lbx_name: &str,
+    image_name: &str,
+    labels: &[String],
+    user_password: Option<&str>,
+    base_image: Option<&str>,
+) -> Result<String> {
+    let dockerfile_path = files::dockerfile_path(lbx_name)?;
+
     let mut cmd = Command::new("podman");
     cmd.args([
         "build",
@@ -259,24 +785,123 @@ pub fn build_image(lbx_name: &str) -> Result<()> {
         "--build-arg",
         &format!("GID={}", getgid().as_raw()),
         "--tag",
-        &image_name,
+        image_name,
         "--label",
         &format!("work.litterbox.name={lbx_name}"),
         "--file",
         dockerfile_path.to_str().expect("Invalid dockerfile_path."),
     ]);
-    trace_arguments(&cmd);
+    for label in labels {
+        cmd.args(["--label", label]);
+    }
+    if let Some(base_image) = base_image {
+        info!("Building from base image {base_image}.");
+        cmd.args(["--build-arg", &format!("BASE_IMAGE={base_image}")]);
+    }
+    if let Some(password) = user_password {
+        cmd.args(["--secret", "id=user_password,env=LBX_USER_PASSWORD"]);
+        cmd.env("LBX_USER_PASSWORD", password);
+    }
+
+    // Have podman tell us the id so that we don't need to look it up again.
+    let iid_path = files::iid_path(lbx_name)?;
+    cmd.arg("--iidfile");
+    cmd.arg(&iid_path);
+
+    let span = trace_arguments(&cmd);
+
+    // The raw output is still useful when debugging or when it is not going
+    // to a terminal anyway.
+    if std::io::stderr().is_terminal() && !log::log_enabled!(log::Level::Debug) {
+        run_build_with_progress(cmd)?;
+    } else {
+        let child = cmd.spawn().context("Failed to run podman command")?;
+        wait_for_podman(child)?;
+    }
+    drop(span);
+    info!("Built image named {image_name}.");
+
+    // Kept around since it tells `image_is_stale` when the build happened
+    let image_id = files::read_file(&iid_path).context("Failed to read built image id")?;
+
+    let image_id = image_id.trim();
+    Ok(image_id
+        .strip_prefix("sha256:")
+        .unwrap_or(image_id)
+        .to_owned())
+}
+
+/// Makes an existing image (e.g. a published dev image) the image of a
+/// Litterbox without a Dockerfile and returns its id. The image gets pulled and
+/// then wrapped in an image that only adds the Litterbox labels.
+pub fn import_image(lbx_name: &str, image_ref: &str, labels: &[String]) -> Result<String> {
+    ensure_local_podman()?;
+    env::wayland_socket_path()?;
+
+    let mut cmd = Command::new("podman");
+    cmd.args(["pull", image_ref]);
+    let span = trace_arguments(&cmd);
     let child = cmd.spawn().context("Failed to run podman command")?;
+    wait_for_podman(child).with_context(|| format!("Failed to pull {image_ref}"))?;
+    drop(span);
+
+    let image_name = get_image(lbx_name)?
+        .and_then(|details| details.names.into_iter().next())
+        .unwrap_or_else(|| podman_name(lbx_name));
+    let iid_path = files::iid_path(lbx_name)?;
 
+    let mut cmd = Command::new("podman");
+    cmd.args([
+        "build",
+        "--pull=never",
+        "--tag",
+        &image_name,
+        "--label",
+        &format!("work.litterbox.name={lbx_name}"),
+    ]);
+    for label in labels {
+        cmd.args(["--label", label]);
+    }
+    cmd.arg("--iidfile");
+    cmd.arg(&iid_path);
+    cmd.args(["--file", "-"]);
+    cmd.stdin(Stdio::piped());
+    cmd.stdout(Stdio::null());
+    let span = trace_arguments(&cmd);
+
+    let mut child = cmd.spawn().context("Failed to run podman command")?;
+    child
+        .stdin
+        .take()
+        .expect("STDIN should be piped")
+        .write_all(format!("FROM {image_ref}\n").as_bytes())
+        .context("Failed to pass the Containerfile to podman")?;
     wait_for_podman(child)?;
-    info!("Built image named {image_name}.");
-    Ok(())
+    drop(span);
+    info!("Imported {image_ref} as {image_name}.");
+
+    // Kept around since it tells `image_is_stale` when the build happened
+    let image_id = files::read_file(&iid_path).context("Failed to read built image id")?;
+
+    let image_id = image_id.trim();
+    Ok(image_id
+        .strip_prefix("sha256:")
+        .unwrap_or(image_id)
+        .to_owned())
 }
 
-pub fn build_litterbox(lbx_name: &str) -> Result<()> {
-    let image_details = get_image(lbx_name)?
-        .ok_or_else(|| anyhow!("No image found for '{lbx_name}'. Run `litterbox build` first."))?;
-    let image_id = image_details.id;
+/// Creates the container of a Litterbox from `image_id`, asking before an
+/// existing one gets replaced. `devices` are recorded in the settings and
+/// passed through to the new container, and the home directory starts out as
+/// a copy of `home_source` if given.
+pub fn build_litterbox(
+    lbx_name: &str,
+    image_id: &str,
+    labels: &[String],
+    devices: &[AttachedDevice],
+    annotations: &[(String, String)],
+    home_source: Option<&Path>,
+) -> Result<()> {
     let container_name = match get_container(lbx_name)? {
         Some(mut details) => {
             assert!(
@@ -289,6 +914,10 @@ pub fn build_litterbox(lbx_name: &str) -> Result<()> {
             }
 
             eprintln!("A container for this Litterbox already exists.");
+            ensure_interactive(
+                "Deciding whether to replace the container",
+                "Delete the Litterbox with `litterbox delete --keep-image` first to replace it.",
+            )?;
 
             if Confirm::new("Would you like to replace this container?")
                 .with_default(true)
@@ -303,18 +932,225 @@ pub fn build_litterbox(lbx_name: &str) -> Result<()> {
         None => podman_name(lbx_name),
     };
 
-    // --userns=keep-id is used, so this is fine to be used in the container.
-    let uid = getuid();
+    create_built_container(
+        lbx_name,
+        &container_name,
+        image_id,
+        labels,
+        devices,
+        annotations,
+        home_source,
+    )
+}
 
-    let rt_dir = PathBuf::from(&format!("/run/user/{uid}"));
-    let wayland_display = env::wayland_display()?;
-    let host_rt_dir = env::xdg_runtime_dir()?;
+/// Replaces the container of a Litterbox with one created from `image_id`
+/// without prompting, stopping the old container first if it is running.
+/// Saved settings are reused and only prompted for when there are none yet.
+pub fn replace_litterbox(
+    lbx_name: &str,
+    image_id: &str,
+    labels: &[String],
+    devices: &[AttachedDevice],
+    annotations: &[(String, String)],
+    home_source: Option<&Path>,
+) -> Result<()> {
+    let container_name = match get_container(lbx_name)? {
+        Some(container) => {
+            if container.state == ContainerState::Running {
+                eprintln!("Stopping the running container...");
+
+                let mut cmd = Command::new("podman");
+                cmd.stdout(Stdio::null());
+                cmd.args(["stop", &container.id]);
+                let span = trace_arguments(&cmd);
+                let child = cmd.spawn().context("Failed to run podman command")?;
+
+                wait_for_podman(child)?;
+                drop(span);
+            }
+
+            // The agent of the old container still holds on to the socket
+            // that the new one gets
+            if daemon::is_running(lbx_name)? {
+                eprintln!("Waiting for the SSH agent to stop...");
+                daemon::wait_for_exit(lbx_name)?;
+            }
+
+            container
+                .names
+                .first()
+                .cloned()
+                .unwrap_or_else(|| podman_name(lbx_name))
+        }
+
+        None => podman_name(lbx_name),
+    };
+
+    // The old container gets removed by `--replace`
+    create_built_container(
+        lbx_name,
+        &container_name,
+        image_id,
+        labels,
+        devices,
+        annotations,
+        home_source,
+    )
+}
+
+/// The steps that building and replacing a Litterbox share once the name of
+/// its container is known: settings are prompted for when there are none yet
+/// and record the devices and annotations of the build, the home directory
+/// gets seeded from `home_source`, and the default keys get attached to the
+/// new container before its post-create hook runs.
+fn create_built_container(
+    lbx_name: &str,
+    container_name: &str,
+    image_id: &str,
+    labels: &[String],
+    devices: &[AttachedDevice],
+    annotations: &[(String, String)],
+    home_source: Option<&Path>,
+) -> Result<()> {
+    let mut settings = LitterboxSettings::load_or_prompt(lbx_name)?;
+    for device in devices {
+        settings.record_device(lbx_name, device.clone())?;
+    }
+    if !annotations.is_empty() {
+        settings.record_annotations(lbx_name, annotations)?;
+    }
+    if home_source.is_some() {
+        ensure!(
+            settings.mount_home,
+            "'{lbx_name}' does not mount a home directory, so there is nothing to copy into"
+        );
+    }
+    create_container(lbx_name, container_name, image_id, labels, &settings)?;
+
+    // The hook may well want to use what is in the home directory
+    if let Some(home_source) = home_source {
+        eprintln!("Copying {home_source:?}...");
+        files::copy_dir_contents(home_source, &files::lbx_home_path(lbx_name)?)?;
+    }
+
+    // Default keys are only relevant when a keys file already exists
+    if let Some(mut keys) = Keys::load_if_exists()? {
+        keys.attach_defaults(lbx_name)?;
+    }
+
+    run_post_create_hook(lbx_name, container_name, &settings)
+}
+
+/// Recreates the container of a Litterbox from its existing image using the
+/// saved settings, without prompting. The image and home directory are left
+/// untouched. Labels given at build time are kept since the image carries them.
+pub fn recreate_litterbox(lbx_name: &str) -> Result<()> {
+    recreate_litterbox_with(lbx_name, &[])?;
+    eprintln!("Litterbox '{lbx_name}' has been recreated.");
+
+    Ok(())
+}
+
+/// Like `recreate_litterbox`, but passes extra arguments to `podman create`
+/// on top of the custom ones from the settings (e.g. temporary limits).
+pub fn recreate_litterbox_with(lbx_name: &str, extra_args: &[String]) -> Result<()> {
+    ensure_local_podman()?;
+
+    let container = require_container(lbx_name)?;
+    if container.state == ContainerState::Running {
+        bail!("Litterbox '{lbx_name}' is running. Exit all of its sessions before recreating it.");
+    }
+
+    let mut settings = LitterboxSettings::load(lbx_name)?.ok_or_else(|| {
+        anyhow!("No settings found for Litterbox '{lbx_name}'. Use `litterbox build` instead.")
+    })?;
+
+    if !extra_args.is_empty() {
+        let custom_args: Vec<String> = settings
+            .custom_podman_args
+            .take()
+            .into_iter()
+            .chain(extra_args.iter().cloned())
+            .collect();
+        settings.custom_podman_args = Some(custom_args.join(" "));
+    }
 
-    let lbx_home_path = files::lbx_home_path(lbx_name)?;
-    fs::create_dir_all(&lbx_home_path).context("Failed to create litterbox home directory")?;
+    let container_name = container
+        .names
+        .first()
+        .cloned()
+        .unwrap_or_else(|| podman_name(lbx_name));
+
+    // The old container gets removed by `--replace`
+    create_container(
+        lbx_name,
+        &container_name,
+        &container.image_id,
+        &[],
+        &settings,
+    )?;
+    devices::restore_devices(lbx_name, &settings)?;
+
+    Ok(())
+}
+
+fn create_container(
+    lbx_name: &str,
+    container_name: &str,
+    image_id: &str,
+    labels: &[String],
+    settings: &LitterboxSettings,
+) -> Result<()> {
+    run_podman_create(
+        lbx_name,
+        container_name,
+        image_id,
+        labels,
+        settings,
+        "label=disable",
+    )?;
+    if !settings.selinux_policy {
+        return Ok(());
+    }
+
+    // udica derives the policy from the mounts and devices of the container,
+    // so the container has to exist before it can be labelled.
+    match selinux::generate_policy(lbx_name, container_name) {
+        Ok(selinux_type) => run_podman_create(
+            lbx_name,
+            container_name,
+            image_id,
+            labels,
+            settings,
+            &format!("label=type:{selinux_type}"),
+        ),
+        Err(e) => {
+            warn!("Could not generate an SELinux policy, so labelling stays disabled: {e:#}");
+            Ok(())
+        }
+    }
+}
+
+fn run_podman_create(
+    lbx_name: &str,
+    container_name: &str,
+    image_id: &str,
+    labels: &[String],
+    settings: &LitterboxSettings,
+    security_label: &str,
+) -> Result<()> {
+    // --userns=keep-id maps us to this user inside the container
+    let (uid, gid) = settings.container_user();
+
+    let rt_dir = PathBuf::from(&format!("/run/user/{uid}"));
+    let wayland_socket = env::wayland_socket_path()?;
+    let wayland_display = wayland_socket
+        .file_name()
+        .expect("Wayland socket path should have a file name")
+        .to_string_lossy()
+        .into_owned();
 
     let ssh_sock = SshSockFile::new(lbx_name, true)?;
-    let settings = LitterboxSettings::load_or_prompt(lbx_name)?;
 
     let session_lock_file_path = files::session_lock_path(lbx_name)?;
 
@@ -329,7 +1165,15 @@ pub fn build_litterbox(lbx_name: &str) -> Result<()> {
     cmd.arg("create");
 
     cmd.arg("--replace");
-    cmd.args(["--entrypoint", "[\"/lbx-init\", \"wait\"]"]);
+    if settings.interactive {
+        cmd.args(["--entrypoint", "[\"/lbx-init\", \"wait\"]"]);
+    } else {
+        debug!("Litterbox will keep running after its last session exits");
+        cmd.args([
+            "--entrypoint",
+            "[\"/lbx-init\", \"wait\", \"--keep-running\"]",
+        ]);
+    }
     cmd.args(["--env", &format!("HOME=/home/{LBX_USER}")]);
     // Allow user to specify RUST_LOG to litterbox internal commands. Useful for
     // development and for debugging.
@@ -344,12 +1188,22 @@ pub fn build_litterbox(lbx_name: &str) -> Result<()> {
     ]);
     cmd.args(["--env", "XDG_SESSION_TYPE=wayland"]);
     cmd.args(["--env", &format!("WAYLAND_DISPLAY={wayland_display}")]);
-    cmd.args(["--hostname", &format!("lbx-{lbx_name}")]);
+    let hostname = settings
+        .hostname
+        .clone()
+        .unwrap_or_else(|| format!("lbx-{lbx_name}"));
+    cmd.args(["--hostname", &hostname]);
     cmd.args(["--label", &format!("work.litterbox.name={lbx_name}")]);
-    cmd.args(["--name", &container_name]);
+    for label in labels {
+        cmd.args(["--label", label]);
+    }
+    for (key, value) in &settings.annotations {
+        cmd.args(["--annotation", &format!("{key}={value}")]);
+    }
+    cmd.args(["--name", container_name]);
     cmd.args(["--network", settings.network_mode.podman_args()]);
-    cmd.args(["--security-opt", "label=disable"]);
-    cmd.args(["--userns", "keep-id"]);
+    cmd.args(["--security-opt", security_label]);
+    cmd.args(["--userns", &format!("keep-id:uid={uid},gid={gid}")]);
 
     // The `wait` command uses it to know when it can exit.
     let mut session_lock_mount = session_lock_file_path.into_os_string();
@@ -372,9 +1226,7 @@ pub fn build_litterbox(lbx_name: &str) -> Result<()> {
     cmd.arg("--volume");
     cmd.arg(ssh_sock_mount);
 
-    let mut wayland_display_mount = OsString::from(&host_rt_dir);
-    wayland_display_mount.push("/");
-    wayland_display_mount.push(&wayland_display);
+    let mut wayland_display_mount = wayland_socket.into_os_string();
     wayland_display_mount.push(":");
     wayland_display_mount.push(&rt_dir);
     wayland_display_mount.push("/");
@@ -383,29 +1235,28 @@ pub fn build_litterbox(lbx_name: &str) -> Result<()> {
     cmd.arg("--volume");
     cmd.arg(wayland_display_mount);
 
-    let mut home_mount = lbx_home_path.into_os_string();
-    home_mount.push(":/home/");
-    home_mount.push(LBX_USER);
-
-    cmd.arg("--volume");
-    cmd.arg(home_mount);
+    if settings.mount_home {
+        let lbx_home_path = files::lbx_home_path(lbx_name)?;
+        fs::create_dir_all(&lbx_home_path).context("Failed to create litterbox home directory")?;
 
-    match GpuDevice::try_detect() {
-        Some(dev) => {
-            debug!("Appending GPU device args for '{}'", dev.device_path());
-            cmd.args(["--volume", dev.volume_mount()]);
-            cmd.args(["--device", dev.device_path()]);
-        }
+        let mut home_mount = lbx_home_path.into_os_string();
+        home_mount.push(":/home/");
+        home_mount.push(LBX_USER);
 
-        None => {
-            warn!("No GPU device found! GPU acceleration will not be available in the Litterbox.")
-        }
+        cmd.arg("--volume");
+        cmd.arg(home_mount);
+    } else {
+        // The home directory from the image gets used, which the Dockerfile
+        // already gave to the user that --userns=keep-id maps us to.
+        debug!("Not mounting a home directory from the host");
     }
 
+    cmd.args(device_args(settings, GpuDevice::try_detect()));
+
     if settings.expose_pipewire {
         let mut pipewire_mount = files::pipewire_socket_path()?.into_os_string();
         pipewire_mount.push(":");
-        pipewire_mount.push(rt_dir);
+        pipewire_mount.push(&rt_dir);
         pipewire_mount.push("/pipewire-0");
 
         debug!("Appending PipeWire socket args");
@@ -413,9 +1264,58 @@ pub fn build_litterbox(lbx_name: &str) -> Result<()> {
         cmd.arg(pipewire_mount);
     }
 
-    if settings.support_tuntap {
-        debug!("Appending TUN/TAP args");
-        cmd.args(["--device", "/dev/net/tun"]);
+    if settings.expose_dbus {
+        let dbus_socket = files::dbus_socket_path()?;
+        if dbus_socket.exists() {
+            let mut dbus_mount = dbus_socket.into_os_string();
+            dbus_mount.push(":");
+            dbus_mount.push(&rt_dir);
+            dbus_mount.push("/bus");
+
+            debug!("Appending D-Bus socket args");
+            cmd.arg("--volume");
+            cmd.arg(dbus_mount);
+            cmd.args([
+                "--env",
+                &format!(
+                    "DBUS_SESSION_BUS_ADDRESS=unix:path={}/bus",
+                    rt_dir.to_string_lossy()
+                ),
+            ]);
+        } else {
+            warn!("D-Bus socket not found, so it will not be exposed.");
+        }
+    }
+
+    let shared_files = [
+        (
+            settings.share_gitconfig,
+            files::host_gitconfig_path()?,
+            ".gitconfig",
+        ),
+        (
+            settings.share_known_hosts,
+            files::host_known_hosts_path()?,
+            ".ssh/known_hosts",
+        ),
+    ];
+    for (_, host_path, home_path) in shared_files.iter().filter(|(share, _, _)| *share) {
+        if !host_path.exists() {
+            debug!("{host_path:?} does not exist, not sharing it");
+            continue;
+        }
+
+        let mut mount = host_path.clone().into_os_string();
+        mount.push(format!(":/home/{LBX_USER}/{home_path}:ro"));
+
+        debug!("Appending shared file args for {host_path:?}");
+        cmd.arg("--volume");
+        cmd.arg(mount);
+    }
+
+    if settings.support_tuntap {
+        debug!("Appending TUN/TAP args");
+        cmd.args(["--device", "/dev/net/tun"]);
         cmd.args(["--cap-add", "NET_ADMIN"]);
     }
 
@@ -435,21 +1335,41 @@ pub fn build_litterbox(lbx_name: &str) -> Result<()> {
         cmd.args(["--group-add", "keep-groups"]);
     }
 
+    for group in &settings.extra_groups {
+        debug!("Appending group-add args: {group}");
+        cmd.args(["--group-add", group]);
+    }
+
     if settings.unconfine_seccomp {
         debug!("Disabling seccomp confinement");
         cmd.args(["--security-opt", "seccomp=unconfined"]);
     }
 
-    if settings.expose_kfd {
-        debug!("Appending KFD device args");
-        cmd.args(["--device", "/dev/kfd"]);
-    }
-
     if let Some(shm_size) = settings.shm_size_gb.map(|gb| format!("{gb}G")) {
         debug!("Appending shm-size args: {shm_size}");
         cmd.args(["--shm-size", &shm_size]);
     }
 
+    if let Some(pids_limit) = settings.pids_limit {
+        debug!("Appending pids-limit args: {pids_limit}");
+        cmd.args(["--pids-limit", &pids_limit.to_string()]);
+    }
+
+    for ulimit in &settings.ulimits {
+        debug!("Appending ulimit args: {ulimit}");
+        cmd.args(["--ulimit", ulimit]);
+    }
+
+    if let Some(ref timezone) = settings.timezone {
+        debug!("Appending timezone args: {timezone}");
+        cmd.args(["--env", &format!("TZ={timezone}")]);
+
+        // Also share the host's zone info in case the image lacks tzdata.
+        if env::host_timezone().as_ref() == Some(timezone) && Path::new("/etc/localtime").exists() {
+            cmd.args(["--volume", "/etc/localtime:/etc/localtime:ro"]);
+        }
+    }
+
     if let Some(ref custom_args) = settings.custom_podman_args {
         debug!("Appending custom podman args: {custom_args}");
         for arg in custom_args.split_whitespace() {
@@ -458,17 +1378,62 @@ pub fn build_litterbox(lbx_name: &str) -> Result<()> {
     }
 
     // It's best to have the image_id as the final argument
-    cmd.arg(&image_id);
+    cmd.arg(image_id);
 
-    trace_arguments(&cmd);
+    let span = trace_arguments(&cmd);
     let child = cmd.spawn().context("Failed to run podman command")?;
     wait_for_podman(child)?;
+    drop(span);
 
     info!("Created container '{container_name}'.");
     Ok(())
 }
 
+/// The `podman create` arguments for the devices that get passed through,
+/// with `gpu` being the GPU device found on the host (if any)
+fn device_args(settings: &LitterboxSettings, gpu: Option<GpuDevice>) -> Vec<String> {
+    let mut args = Vec::new();
+
+    match gpu {
+        _ if !settings.expose_dri => debug!("Not exposing the GPU"),
+
+        Some(dev) => {
+            debug!("Appending GPU device args for '{}'", dev.device_path());
+            args.extend(dev.podman_args().map(String::from));
+        }
+
+        None => {
+            warn!("No GPU device found! GPU acceleration will not be available in the Litterbox.")
+        }
+    }
+
+    if settings.expose_kfd {
+        debug!("Appending KFD device args");
+        args.extend(["--device".to_owned(), KFD_DEVICE.to_owned()]);
+    }
+
+    if settings.expose_kvm {
+        debug!("Appending KVM device args");
+        args.extend(["--device".to_owned(), KVM_DEVICE.to_owned()]);
+    }
+
+    for device in settings.attached_devices.iter().filter(|d| d.passthrough) {
+        if Path::new(&device.path).exists() {
+            debug!("Appending device args for {}", device.path);
+            args.extend(["--device".to_owned(), device.path.clone()]);
+        } else {
+            warn!(
+                "Device {} does not exist, so it will not be attached.",
+                device.path
+            );
+        }
+    }
+
+    args
+}
+
 pub fn start_daemon(lbx_name: &str) -> Result<(), anyhow::Error> {
+    let _span = tracing::info_span!("start_daemon", lbx_name).entered();
     let keys = Keys::load()?;
     let password = keys.password_if_needed(lbx_name)?;
     let log_file_out = files::daemon_log_file(lbx_name)?;
@@ -495,40 +1460,279 @@ pub fn start_daemon(lbx_name: &str) -> Result<(), anyhow::Error> {
     Ok(())
 }
 
-pub fn delete_litterbox(lbx_name: &str) -> Result<()> {
-    let container =
-        get_container(lbx_name)?.ok_or_else(|| anyhow!("No container found for {}", lbx_name))?;
-    let container_id = container.id;
+fn remove_container_and_image(lbx_name: &str, container_id: &str, keep_image: bool) -> Result<()> {
+    let mut cmd = Command::new("podman");
+    cmd.args(["rm", container_id]);
+    let span = trace_arguments(&cmd);
+    let child = cmd.spawn().context("Failed to run podman command")?;
 
-    let should_delete = Confirm::new("Are you sure you want to delete this Litterbox?")
-        .with_default(false)
-        .with_help_message(
-            "This operation cannot be undone and will delete all data/state outside the home directory.",
-        )
-        .prompt();
+    wait_for_podman(child)?;
+    drop(span);
+    info!("Container for Litterbox deleted!");
+
+    // A new container should get set up by the hook again
+    remove_post_create_hook_marker(lbx_name)?;
+
+    if keep_image {
+        eprintln!("Keeping the image for this Litterbox.");
+    } else {
+        let image_details =
+            get_image(lbx_name)?.ok_or_else(|| anyhow!("No image found for {}", lbx_name))?;
+        let mut cmd = Command::new("podman");
+        cmd.args(["image", "rm", &image_details.id]);
+        let span = trace_arguments(&cmd);
+        let child = cmd.spawn().context("Failed to run podman command")?;
+
+        wait_for_podman(child)?;
+        drop(span);
+        info!("Image for Litterbox deleted!");
+    }
+
+    Ok(())
+}
+
+/// Rebuilds the image of a Litterbox and recreates its container from it
+/// without prompting. The home directory, settings and labels are kept.
+pub fn rebuild_litterbox(container: &Container, user_password: Option<&str>) -> Result<()> {
+    let lbx_name = &container.labels.name;
+    if container.state == ContainerState::Running {
+        bail!("It is running. Exit all of its sessions first.");
+    }
+
+    let settings = LitterboxSettings::load(lbx_name)?
+        .ok_or_else(|| anyhow!("It has no settings. Use `litterbox build` instead."))?;
 
-    if !should_delete.is_ok_and(|x| x) {
-        eprintln!("Okay, the Litterbox won't be deleted!");
+    let dockerfile_path = files::dockerfile_path(lbx_name)?;
+    ensure!(
+        dockerfile_path.exists(),
+        "Its Dockerfile {dockerfile_path:?} does not exist"
+    );
+
+    let image_name = get_image(lbx_name)?
+        .and_then(|details| details.names.into_iter().next())
+        .unwrap_or_else(|| podman_name(lbx_name));
+    // Only passed on when the Dockerfile asks for it
+    let user_password = match user_password {
+        Some(password) if dockerfile_needs_user_password(lbx_name)? => Some(password),
+        _ => None,
+    };
+    let labels = container.labels.user_labels();
+    let image_id = run_image_build(
+        lbx_name,
+        &image_name,
+        &labels,
+        user_password,
+        settings.base_image.as_deref(),
+    )?;
+
+    let container_name = container
+        .names
+        .first()
+        .cloned()
+        .unwrap_or_else(|| podman_name(lbx_name));
+
+    // The old container gets removed by `--replace`
+    create_container(lbx_name, &container_name, &image_id, &labels, &settings)
+}
+
+/// Rebuilds the images and recreates the containers of all Litterboxes whose
+/// names match the optional glob pattern, carrying on past failures.
+pub fn rebuild_all_litterboxes(pattern: Option<&str>, user_password: Option<&str>) -> Result<()> {
+    // Fail before spending time on builds that could never be used
+    ensure_local_podman()?;
+    env::wayland_socket_path()?;
+
+    let containers = get_containers(&[])?.matching(pattern).0;
+    if containers.is_empty() {
+        eprintln!("No matching Litterboxes found.");
         return Ok(());
     }
 
-    let mut cmd = Command::new("podman");
-    cmd.args(["rm", &container_id]);
-    trace_arguments(&cmd);
-    let child = cmd.spawn().context("Failed to run podman command")?;
+    eprintln!("The following Litterboxes will be rebuilt:");
+    for container in &containers {
+        eprintln!("  {}", container.labels.name);
+    }
 
-    wait_for_podman(child)?;
-    info!("Container for Litterbox deleted!");
+    if !assume_yes() {
+        ensure_interactive(
+            "Confirming the rebuild",
+            "Pass --yes to confirm the rebuild up front.",
+        )?;
+        let should_rebuild = Confirm::new("Are you sure you want to rebuild these Litterboxes?")
+            .with_default(false)
+            .with_help_message(
+                "All data/state outside the home directories will be lost. Settings are kept.",
+            )
+            .prompt()?;
+
+        if !should_rebuild {
+            eprintln!("Okay, no Litterboxes will be rebuilt!");
+            return Ok(());
+        }
+    }
 
-    let image_details =
-        get_image(lbx_name)?.ok_or_else(|| anyhow!("No image found for {}", lbx_name))?;
-    let mut cmd = Command::new("podman");
-    cmd.args(["image", "rm", &image_details.id]);
-    trace_arguments(&cmd);
-    let child = cmd.spawn().context("Failed to run podman command")?;
+    let mut failures = Vec::new();
+    for container in &containers {
+        let name = &container.labels.name;
+        eprintln!("Rebuilding '{name}'...");
+        if let Err(e) = rebuild_litterbox(container, user_password) {
+            failures.push(format!("{name}: {e:#}"));
+        }
+    }
 
-    wait_for_podman(child)?;
-    info!("Image for Litterbox deleted!");
+    eprintln!(
+        "Rebuilt {} of {} Litterboxes.",
+        containers.len() - failures.len(),
+        containers.len()
+    );
+    for failure in &failures {
+        eprintln!("  Failed: {failure}");
+    }
+
+    ensure!(failures.is_empty(), "Some Litterboxes could not be rebuilt");
+    Ok(())
+}
+
+/// Prints what gets removed along with the container of a Litterbox
+fn print_container_removal(lbx_name: &str, container: &Container, keep_image: bool) -> Result<()> {
+    println!("  Container: {}", container.id);
+    if keep_image {
+        println!("  Image (kept): {}", container.image_id);
+    } else {
+        println!("  Image: {} ({})", container.image_id, container.image);
+    }
+
+    let marker = files::post_create_hook_marker_path(lbx_name)?;
+    if marker.exists() {
+        println!("  Post-create hook marker: {marker:?}");
+    }
+
+    Ok(())
+}
+
+/// Lists everything that deleting a Litterbox could remove, without removing
+/// anything. The home directory and definition files are only deleted when
+/// the user confirms that separately.
+pub fn print_deletion_plan(lbx_name: &str, keep_image: bool) -> Result<()> {
+    let container = require_container(lbx_name)?;
+    println!("Deleting '{lbx_name}' would remove:");
+    print_container_removal(lbx_name, &container, keep_image)?;
+
+    let paths = [
+        files::lbx_home_path(lbx_name)?,
+        files::dockerfile_path(lbx_name)?,
+        files::settings_path(lbx_name)?,
+    ];
+    let existing: Vec<_> = paths.iter().filter(|path| path.exists()).collect();
+    if !existing.is_empty() {
+        println!("It would then ask before deleting:");
+        for path in existing {
+            println!("  {path:?}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Lists the containers and images that deleting all (matching) Litterboxes
+/// would remove, without removing anything
+pub fn print_deletion_plan_all(pattern: Option<&str>, keep_image: bool) -> Result<()> {
+    let containers = get_containers(&[])?.matching(pattern).0;
+    if containers.is_empty() {
+        eprintln!("No matching Litterboxes found.");
+        return Ok(());
+    }
+
+    for container in &containers {
+        let name = &container.labels.name;
+        println!("Deleting '{name}' would remove:");
+        print_container_removal(name, container, keep_image)?;
+    }
+
+    Ok(())
+}
+
+/// Deletes the containers and images of all Litterboxes whose names match the
+/// optional glob pattern. Home directories and definitions are kept.
+pub fn delete_all_litterboxes(pattern: Option<&str>, keep_image: bool) -> Result<()> {
+    let containers = get_containers(&[])?.matching(pattern);
+    let any_running = containers.running().next().is_some();
+    let containers = containers.0;
+    if containers.is_empty() {
+        eprintln!("No matching Litterboxes found.");
+        return Ok(());
+    }
+
+    eprintln!("The following Litterboxes will be deleted:");
+    for container in &containers {
+        eprintln!("  {}", container.labels.name);
+    }
+    if any_running {
+        eprintln!("Running ones will fail to be deleted until all of their sessions have exited.");
+    }
+
+    if !assume_yes() {
+        ensure_interactive(
+            "Confirming the deletion",
+            "Pass --yes to confirm the deletion up front.",
+        )?;
+        let should_delete = Confirm::new("Are you sure you want to delete these Litterboxes?")
+            .with_default(false)
+            .with_help_message(
+                "This operation cannot be undone. Home directories and definition files will be kept.",
+            )
+            .prompt()?;
+
+        if !should_delete {
+            eprintln!("Okay, no Litterboxes will be deleted!");
+            return Ok(());
+        }
+    }
+
+    let mut failures = Vec::new();
+    for container in &containers {
+        let name = &container.labels.name;
+        if let Err(e) = remove_container_and_image(name, &container.id, keep_image) {
+            failures.push(format!("{name}: {e:#}"));
+        }
+    }
+
+    eprintln!(
+        "Deleted {} of {} Litterboxes.",
+        containers.len() - failures.len(),
+        containers.len()
+    );
+    for failure in &failures {
+        eprintln!("  Failed: {failure}");
+    }
+
+    ensure!(failures.is_empty(), "Some Litterboxes could not be deleted");
+    Ok(())
+}
+
+pub fn delete_litterbox(lbx_name: &str, keep_image: bool) -> Result<()> {
+    let container = require_container(lbx_name)?;
+    let container_id = container.id;
+
+    if !assume_yes() {
+        ensure_interactive(
+            "Confirming the deletion",
+            "Pass --yes to confirm the deletion up front.",
+        )?;
+        let should_delete = Confirm::new("Are you sure you want to delete this Litterbox?")
+            .with_default(false)
+            .with_help_message(
+                "This operation cannot be undone and will delete all data/state outside the home directory.",
+            )
+            .prompt();
+
+        if !should_delete.is_ok_and(|x| x) {
+            eprintln!("Okay, the Litterbox won't be deleted!");
+            return Ok(());
+        }
+    }
+
+    remove_container_and_image(lbx_name, &container_id, keep_image)?;
 
     let home_path = files::lbx_home_path(lbx_name)?;
     if home_path.exists() {
@@ -582,14 +1786,657 @@ pub fn delete_litterbox(lbx_name: &str) -> Result<()> {
     Ok(())
 }
 
+/// Runs a podman command to completion and captures its output. The command
+/// gets killed if it does not finish within the configured timeout so that a
+/// wedged podman (e.g. stuck on a storage lock) cannot freeze Litterbox.
+fn podman_output(mut cmd: Command) -> Result<Output> {
+    let timeout = env::podman_timeout();
+
+    cmd.stdin(Stdio::null());
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+    let _span = trace_arguments(&cmd);
+
+    let child = cmd.spawn().context("Failed to run podman command")?;
+    let pid = Pid::from_raw(child.id() as i32);
+    let (tx, rx) = mpsc::sync_channel(1);
+
+    std::thread::spawn(move || {
+        let _ = tx.send(child.wait_with_output());
+    });
+
+    match rx.recv_timeout(timeout) {
+        Ok(output) => output.context("Failed to run podman command"),
+        Err(RecvTimeoutError::Timeout) => {
+            let _ = kill(pid, Signal::SIGKILL);
+
+            bail!(
+                "Podman did not respond within {} seconds and was stopped.\n\
+                 It might be stuck waiting on a storage lock; try running `podman ps` to check.\n\
+                 The timeout can be changed by setting {}.",
+                timeout.as_secs(),
+                env::PODMAN_TIMEOUT_VAR,
+            )
+        }
+        Err(RecvTimeoutError::Disconnected) => bail!("Podman command ended unexpectedly"),
+    }
+}
+
+/// A podman object (container or image) that the user can choose to keep
+struct RepairChoice {
+    id: String,
+    created: i64,
+    description: String,
+}
+
+impl std::fmt::Display for RepairChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (created {}) {}",
+            &self.id[..self.id.len().min(12)],
+            format_age(self.created),
+            self.description
+        )
+    }
+}
+
+/// Asks which of the conflicting objects to keep and returns the others.
+/// The newest one is kept automatically when `--yes` is used.
+fn choose_extras(kind: &str, mut choices: Vec<RepairChoice>) -> Result<Vec<RepairChoice>> {
+    choices.sort_by_key(|c| std::cmp::Reverse(c.created));
+
+    eprintln!("Found {} {kind}s for this Litterbox:", choices.len());
+    for choice in &choices {
+        eprintln!("  {choice}");
+    }
+
+    let keep = if assume_yes() {
+        0
+    } else {
+        ensure_interactive(
+            &format!("Choosing which {kind} to keep"),
+            "Pass --yes to keep the newest one.",
+        )?;
+        let options = choices.iter().map(|c| c.to_string()).collect();
+        inquire::Select::new(&format!("Which {kind} do you want to keep?"), options)
+            .with_help_message("The others will be deleted.")
+            .raw_prompt()?
+            .index
+    };
+
+    choices.remove(keep);
+    Ok(choices)
+}
+
+/// Removes the Litterbox images that no container uses (e.g. the ones left
+/// behind by rebuilds) and optionally the home directories of Litterboxes
+/// that no longer exist. When `older_than` is given, only things that were
+/// created (or for homes, last modified) before then are removed.
+pub fn prune_litterboxes(older_than: Option<std::time::Duration>, homes: bool) -> Result<()> {
+    let cutoff = older_than
+        .map(|age| {
+            std::time::SystemTime::now()
+                .checked_sub(age)
+                .context("The age to prune by is too long")
+        })
+        .transpose()?;
+    let is_old = |time: std::time::SystemTime| cutoff.is_none_or(|cutoff| time < cutoff);
+
+    let containers = get_containers(&[])?;
+
+    let images = get_litterbox_images()?;
+
+    let unused_images: Vec<Image> = images
+        .into_iter()
+        .filter(|image| !containers.0.iter().any(|c| c.image_id == image.id))
+        .filter(|image| {
+            let created =
+                std::time::UNIX_EPOCH + std::time::Duration::from_secs(image.created.max(0) as u64);
+            is_old(created)
+        })
+        .collect();
+
+    let mut orphaned_homes = Vec::new();
+    let homes_path = files::homes_path()?;
+    if homes && homes_path.exists() {
+        for entry in fs::read_dir(&homes_path).context("Failed to list home directories")? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let modified = entry.metadata()?.modified()?;
+
+            if containers.find_by_name(&name).is_none() && is_old(modified) {
+                orphaned_homes.push(entry.path());
+            }
+        }
+    }
+
+    if unused_images.is_empty() && orphaned_homes.is_empty() {
+        eprintln!("Nothing to prune.");
+        return Ok(());
+    }
+
+    eprintln!("The following will be deleted:");
+    for image in &unused_images {
+        eprintln!(
+            "  Image {} (created {}) {}",
+            &image.id[..image.id.len().min(12)],
+            format_age(image.created),
+            image.names.join(",")
+        );
+    }
+    for home in &orphaned_homes {
+        eprintln!("  Home directory {home:?}");
+    }
+
+    if !assume_yes() {
+        ensure_interactive(
+            "Confirming the pruning",
+            "Pass --yes to confirm the pruning up front.",
+        )?;
+        let should_prune = Confirm::new("Are you sure you want to delete these?")
+            .with_default(false)
+            .with_help_message("This operation cannot be undone.")
+            .prompt()?;
+
+        if !should_prune {
+            eprintln!("Okay, nothing will be pruned!");
+            return Ok(());
+        }
+    }
+
+    let mut failures = Vec::new();
+    for image in &unused_images {
+        let mut cmd = Command::new("podman");
+        cmd.args(["image", "rm", &image.id]);
+        cmd.stdout(Stdio::null());
+        let span = trace_arguments(&cmd);
+
+        let removed = cmd
+            .spawn()
+            .context("Failed to run podman command")
+            .and_then(wait_for_podman);
+        drop(span);
+        if let Err(e) = removed {
+            failures.push(format!("image {}: {e:#}", image.id));
+        }
+    }
+    for home in &orphaned_homes {
+        if let Err(e) = fs::remove_dir_all(home) {
+            failures.push(format!("{home:?}: {e}"));
+        }
+    }
+
+    let total = unused_images.len() + orphaned_homes.len();
+    eprintln!("Pruned {} of {total}.", total - failures.len());
+    for failure in &failures {
+        eprintln!("  Failed: {failure}");
+    }
+
+    ensure!(failures.is_empty(), "Some things could not be pruned");
+    Ok(())
+}
+
+/// Resolves Litterboxes that have multiple containers or images (e.g. after an
+/// interrupted build) by deleting the extras.
+pub fn repair_litterbox(lbx_name: &str) -> Result<()> {
+    let mut repaired = false;
+
+    let containers = get_containers_by_name(lbx_name)?.0;
+    if containers.len() > 1 {
+        let choices = containers
+            .into_iter()
+            .map(|c| RepairChoice {
+                description: format!("{} [{:?}]", c.names.join(","), c.state),
+                id: c.id,
+                created: c.created,
+            })
+            .collect();
+
+        for extra in choose_extras("container", choices)? {
+            let mut cmd = Command::new("podman");
+            cmd.args(["rm", "--force", &extra.id]);
+            cmd.stdout(Stdio::null());
+            let span = trace_arguments(&cmd);
+            wait_for_podman(cmd.spawn().context("Failed to run podman command")?)?;
+            drop(span);
+            eprintln!("Deleted container {extra}");
+        }
+
+        repaired = true;
+    }
+
+    let images = get_images_by_name(lbx_name)?;
+    if images.len() > 1 {
+        let choices = images
+            .into_iter()
+            .map(|i| RepairChoice {
+                description: i.names.join(","),
+                id: i.id,
+                created: i.created,
+            })
+            .collect();
+
+        for extra in choose_extras("image", choices)? {
+            let mut cmd = Command::new("podman");
+            cmd.args(["image", "rm", &extra.id]);
+            cmd.stdout(Stdio::null());
+            let span = trace_arguments(&cmd);
+            wait_for_podman(cmd.spawn().context("Failed to run podman command")?)?;
+            drop(span);
+            eprintln!("Deleted image {extra}");
+        }
+
+        repaired = true;
+    }
+
+    if repaired {
+        eprintln!("Litterbox '{lbx_name}' has been repaired.");
+    } else {
+        eprintln!("Litterbox '{lbx_name}' has nothing to repair.");
+    }
+
+    Ok(())
+}
+
+/// The post-create hook of a Litterbox did not run successfully
+#[derive(Debug)]
+pub struct PostCreateHookError {
+    pub hook: PathBuf,
+    pub status: ExitStatus,
+    pub output: String,
+}
+
+impl std::fmt::Display for PostCreateHookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The post-create hook {:?} failed ({}). It will run again when the Litterbox \
+             gets rebuilt.\n{}",
+            self.hook,
+            self.status,
+            self.output.trim_end()
+        )
+    }
+}
+
+impl std::error::Error for PostCreateHookError {}
+
+/// Runs the post-create hook of a Litterbox inside its newly created
+/// container, unless it has already run since the Litterbox was created. The
+/// container gets started for it and stopped again afterwards.
+fn run_post_create_hook(
+    lbx_name: &str,
+    container_name: &str,
+    settings: &LitterboxSettings,
+) -> Result<()> {
+    let Some(hook) = &settings.post_create_hook else {
+        return Ok(());
+    };
+
+    let marker = files::post_create_hook_marker_path(lbx_name)?;
+    if marker.exists() {
+        debug!("Post-create hook already ran for {lbx_name}");
+        return Ok(());
+    }
+
+    // Interactive containers stop once they have no sessions left, so the
+    // hook counts as one while it runs
+    let session_lock = files::session_lock_path(lbx_name)?;
+    let my_pid = Pid::this();
+    files::append_pid_to_session_lockfile(&session_lock, my_pid)?;
+
+    let res = start_container(lbx_name, container_name)
+        .and_then(|()| exec_post_create_hook(container_name, hook, settings));
+
+    files::remove_pid_from_session_lockfile(&session_lock, my_pid)?;
+    if !settings.interactive {
+        let mut cmd = Command::new("podman");
+        cmd.args(["stop", container_name]);
+        podman_output(cmd).and_then(|output| extract_stdout(&output).map(|_| ()))?;
+    }
+
+    res?;
+    write_file(&marker, "").context("Failed to record that the post-create hook ran")
+}
+
+fn exec_post_create_hook(
+    container_name: &str,
+    hook: &Path,
+    settings: &LitterboxSettings,
+) -> Result<()> {
+    let (uid, gid) = settings.container_user();
+
+    eprintln!("Running post-create hook {hook:?}...");
+    let script = fs::File::open(hook)
+        .with_context(|| format!("Failed to open post-create hook {hook:?}"))?;
+
+    // The script is fed through STDIN so that its permissions on the host do
+    // not matter inside the container.
+    let mut cmd = Command::new("podman");
+    cmd.args([
+        "exec",
+        "--interactive",
+        "--user",
+        &format!("{uid}:{gid}"),
+        "--workdir",
+        &format!("/home/{LBX_USER}"),
+        container_name,
+        "/bin/sh",
+        "-s",
+    ]);
+    cmd.stdin(script);
+    let span = trace_arguments(&cmd);
+    let output = cmd.output().context("Failed to run podman command")?;
+    drop(span);
+
+    let mut combined = String::from_utf8_lossy(&output.stdout).into_owned();
+    combined.push_str(&String::from_utf8_lossy(&output.stderr));
+
+    if !output.status.success() {
+        return Err(PostCreateHookError {
+            hook: hook.to_owned(),
+            status: output.status,
+            output: combined,
+        }
+        .into());
+    }
+
+    info!("Post-create hook output:\n{combined}");
+    Ok(())
+}
+
+fn remove_post_create_hook_marker(lbx_name: &str) -> Result<()> {
+    let marker = files::post_create_hook_marker_path(lbx_name)?;
+    if marker.exists() {
+        fs::remove_file(&marker).context("Failed to remove post-create hook marker")?;
+    }
+
+    Ok(())
+}
+
+/// Removes everything belonging to a Litterbox without asking any questions.
+/// Failures are only logged since this is used for best-effort cleanup.
+pub fn purge_litterbox(lbx_name: &str) {
+    fn log_failure(what: &str, result: Result<()>) {
+        if let Err(e) = result {
+            error!("Failed to remove {what}: {e:#}");
+        }
+    }
+
+    log_failure(
+        "container",
+        get_container(lbx_name).and_then(|container| {
+            let Some(container) = container else {
+                return Ok(());
+            };
+
+            let mut cmd = Command::new("podman");
+            cmd.args(["rm", "--force", &container.id]);
+            cmd.stdout(Stdio::null());
+            let _span = trace_arguments(&cmd);
+            wait_for_podman(cmd.spawn().context("Failed to run podman command")?)
+        }),
+    );
+
+    log_failure(
+        "image",
+        get_image(lbx_name).and_then(|image| {
+            let Some(image) = image else {
+                return Ok(());
+            };
+
+            let mut cmd = Command::new("podman");
+            cmd.args(["image", "rm", "--force", &image.id]);
+            cmd.stdout(Stdio::null());
+            let _span = trace_arguments(&cmd);
+            wait_for_podman(cmd.spawn().context("Failed to run podman command")?)
+        }),
+    );
+
+    log_failure(
+        "post-create hook marker",
+        remove_post_create_hook_marker(lbx_name),
+    );
+
+    log_failure(
+        "image id file",
+        files::iid_path(lbx_name).and_then(|path| {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+            Ok(())
+        }),
+    );
+
+    log_failure(
+        "home directory",
+        files::lbx_home_path(lbx_name).and_then(|path| {
+            if path.exists() {
+                fs::remove_dir_all(path)?;
+            }
+            Ok(())
+        }),
+    );
+
+    for path in [
+        files::dockerfile_path(lbx_name),
+        files::settings_path(lbx_name),
+    ] {
+        log_failure(
+            "definition file",
+            path.and_then(|path| {
+                if path.exists() {
+                    fs::remove_file(path)?;
+                }
+                Ok(())
+            }),
+        );
+    }
+
+    info!("Litterbox '{lbx_name}' has been removed.");
+}
+
+/// Starts a stopped container. One that was stopped uncleanly can be left with
+/// stale runtime state (e.g. a dead `exec.fifo`) that makes starting it fail,
+/// in which case that state gets cleaned up before trying once more.
+pub fn start_container(lbx_name: &str, container_id: &str) -> Result<()> {
+    let start = || {
+        let mut cmd = Command::new("podman");
+        cmd.args(["start", container_id]);
+        podman_output(cmd).and_then(|output| extract_stdout(&output).map(|_| ()))
+    };
+
+    let Err(e) = start() else {
+        return Ok(());
+    };
+    warn!("Failed to start the container, cleaning up its runtime state and retrying: {e:#}");
+
+    let mut cmd = Command::new("podman");
+    cmd.args(["container", "cleanup", container_id]);
+    if let Err(e) = podman_output(cmd).and_then(|output| extract_stdout(&output).map(|_| ())) {
+        debug!("Cleaning up the container failed: {e:#}");
+    }
+
+    start().with_context(|| {
+        format!(
+            "Could not start '{lbx_name}', even after cleaning up its runtime state.\n\
+             Run `litterbox recreate {lbx_name}` to replace its container."
+        )
+    })
+}
+
 pub fn wait_for_podman(mut child: Child) -> Result<()> {
     let res = child.wait().context("Failed to run podman command")?;
     ensure!(res.success(), "Podman command failed");
     Ok(())
 }
 
-pub async fn wait_for_podman_async(child: &mut tokio::process::Child) -> Result<()> {
-    let res = child.wait().await.context("Failed to run podman command")?;
-    ensure!(res.success(), "Podman command failed");
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_annotations() {
+        assert_eq!(
+            parse_annotation("io.containers.trace-syscall=of:/tmp/policy.json").unwrap(),
+            (
+                "io.containers.trace-syscall".to_owned(),
+                "of:/tmp/policy.json".to_owned()
+            )
+        );
+        assert_eq!(
+            parse_annotation("empty=").unwrap(),
+            ("empty".to_owned(), String::new())
+        );
+        assert!(parse_annotation("no-value").is_err());
+        assert!(parse_annotation("=value").is_err());
+    }
+
+    #[test]
+    fn looks_up_containers() {
+        let container = |name: &str, state: &str| {
+            serde_json::json!({
+                "Id": format!("{name}-id"),
+                "Image": format!("localhost/lbx-{name}:latest"),
+                "ImageID": "image-id",
+                "Names": [format!("lbx-{name}")],
+                "Labels": { "work.litterbox.name": name },
+                "State": state,
+            })
+        };
+        let containers: Containers = serde_json::from_value(serde_json::json!([
+            container("web", "running"),
+            container("api", "exited"),
+            container("web-2", "running"),
+        ]))
+        .unwrap();
+
+        assert_eq!(containers.find_by_name("api").unwrap().id, "api-id");
+        assert!(containers.find_by_name("db").is_none());
+        assert_eq!(
+            containers
+                .running()
+                .map(|c| c.labels.name.as_str())
+                .collect::<Vec<_>>(),
+            ["web", "web-2"]
+        );
+        assert_eq!(containers.names(), ["api", "web", "web-2"]);
+        assert_eq!(containers.matching(Some("web*")).names(), ["web", "web-2"]);
+    }
+
+    #[test]
+    fn keeps_user_labels_apart() {
+        let labels: ContainerLabels = serde_json::from_value(serde_json::json!({
+            "work.litterbox.name": "web",
+            "work.litterbox.other": "internal",
+            "team": "infra",
+            "env": "dev=1",
+        }))
+        .unwrap();
+
+        assert_eq!(labels.name, "web");
+        assert_eq!(labels.user_labels(), ["env=dev=1", "team=infra"]);
+    }
+
+    #[test]
+    fn compares_modification_times() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let modified = fs::metadata(file.path()).unwrap().modified().unwrap();
+        let minute = std::time::Duration::from_secs(60);
+
+        assert!(modified_after(file.path(), modified - minute));
+        assert!(!modified_after(file.path(), modified + minute));
+        assert!(!modified_after(
+            &file.path().with_extension("missing"),
+            std::time::UNIX_EPOCH
+        ));
+    }
+
+    #[test]
+    fn gpu_devices_are_only_passed_once() {
+        let mut settings: LitterboxSettings = ron::from_str(
+            "(version: 1, support_ping: false, support_tuntap: false, \
+             packet_forwarding: false, expose_pipewire: false)",
+        )
+        .unwrap();
+
+        for dev in [GpuDevice::Dri, GpuDevice::Dxg] {
+            let path = dev.device_path();
+            assert_eq!(device_args(&settings, Some(dev)), ["--device", path]);
+        }
+
+        settings.expose_dri = false;
+        assert!(device_args(&settings, Some(GpuDevice::Dri)).is_empty());
+    }
+
+    #[test]
+    fn templates_use_user_password() {
+        use crate::template::Template;
+        use clap::ValueEnum;
+
+        for template in Template::value_variants() {
+            assert!(uses_user_password(template.contents()), "{template}");
+        }
+        assert!(!uses_user_password("FROM alpine\nRUN adduser -D user\n"));
+    }
+
+    #[test]
+    fn templates_take_the_base_image_as_a_build_arg() {
+        use crate::template::Template;
+        use clap::ValueEnum;
+
+        for template in Template::value_variants() {
+            let contents = template.contents();
+            assert!(contents.contains("ARG BASE_IMAGE="), "{template}");
+            assert!(contents.contains("FROM ${BASE_IMAGE}"), "{template}");
+        }
+    }
+
+    #[test]
+    fn prefers_the_image_in_use_and_then_the_newest() {
+        let image = |id: &str, created| Image {
+            id: id.to_owned(),
+            names: Vec::new(),
+            created,
+            size: 0,
+        };
+        let ids = |images: &[Image]| images.iter().map(|i| i.id.clone()).collect::<Vec<_>>();
+
+        let images = vec![image("old", 1), image("new", 3), image("mid", 2)];
+        let (preferred, extras) = split_preferred_image(images, None);
+        assert_eq!(preferred.id, "new");
+        assert_eq!(ids(&extras), ["old", "mid"]);
+
+        let images = vec![image("old", 1), image("new", 3), image("mid", 2)];
+        let (preferred, extras) = split_preferred_image(images, Some("old"));
+        assert_eq!(preferred.id, "old");
+        assert_eq!(ids(&extras), ["mid", "new"]);
+    }
+
+    #[test]
+    fn parses_build_steps() {
+        let step = BuildStep::parse("STEP 3/12: RUN apt-get update").unwrap();
+        assert_eq!(
+            step,
+            BuildStep {
+                current: 3,
+                total: 12,
+                instruction: "RUN apt-get update"
+            }
+        );
+        assert_eq!(step.to_string(), "[3/12] RUN apt-get update");
+
+        let staged = BuildStep::parse("[1/2] STEP 2/5: COPY . /src").unwrap();
+        assert_eq!((staged.current, staged.total), (2, 5));
+
+        let long = format!("STEP 1/1: RUN {}", "x".repeat(100));
+        let shown = BuildStep::parse(&long).unwrap().to_string();
+        assert!(shown.ends_with("..."), "{shown}");
+        assert!(shown.len() < 80, "{shown}");
+
+        assert!(BuildStep::parse("COMMIT litterbox-dev").is_none());
+        assert!(BuildStep::parse("Getting image source signatures").is_none());
+        assert!(BuildStep::parse("STEP x/y: RUN true").is_none());
+    }
 }
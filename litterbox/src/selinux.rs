@@ -0,0 +1,73 @@
+use anyhow::{Context, Result, ensure};
+use std::{path::Path, process::Command};
+
+use crate::{
+    files::{selinux_policies_path, write_file},
+    utils::{extract_stdout, trace_arguments},
+};
+
+/// Only present when SELinux is enabled on the host
+const SELINUX_ENFORCE: &str = "/sys/fs/selinux/enforce";
+
+pub fn selinux_enabled() -> bool {
+    Path::new(SELINUX_ENFORCE).exists()
+}
+
+fn udica_installed() -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join("udica").is_file()))
+}
+
+/// Name of the SELinux module for a Litterbox. Module names may only contain
+/// letters, digits and underscores.
+fn policy_name(lbx_name: &str) -> String {
+    let name: String = lbx_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("lbx_{name}")
+}
+
+/// Has udica generate a policy for a container that only allows access to its
+/// mounts and devices, and loads it. Returns the SELinux type that the
+/// container should be labelled with.
+pub fn generate_policy(lbx_name: &str, container_name: &str) -> Result<String> {
+    ensure!(udica_installed(), "udica is not installed");
+
+    let mut cmd = Command::new("podman");
+    cmd.args(["inspect", container_name]);
+    let span = trace_arguments(&cmd);
+    let output = cmd.output().context("Failed to run podman command")?;
+    drop(span);
+
+    let policy = policy_name(lbx_name);
+    let policies_dir = selinux_policies_path()?;
+    let inspect_path = policies_dir.join(format!("{policy}.json"));
+    write_file(&inspect_path, extract_stdout(&output)?)?;
+
+    eprintln!(
+        "Root permissions are required to load the SELinux policy. Please enter your password if prompted."
+    );
+    let mut cmd = Command::new("sudo");
+    cmd.current_dir(&policies_dir);
+    cmd.arg("udica");
+    cmd.arg("--json");
+    cmd.arg(&inspect_path);
+    cmd.args(["--load-modules", &policy]);
+    let _span = trace_arguments(&cmd);
+    let res = cmd.status().context("Failed to run udica")?;
+    ensure!(res.success(), "udica failed to generate the policy");
+
+    Ok(format!("{policy}.process"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_policies_after_litterboxes() {
+        assert_eq!(policy_name("dev"), "lbx_dev");
+        assert_eq!(policy_name("my-box.2"), "lbx_my_box_2");
+    }
+}
@@ -1,11 +1,37 @@
-use anyhow::{Context, Result, anyhow};
+use anyhow::{Context, Result, anyhow, bail};
 use inquire::{Confirm, Text};
 use inquire_derive::Selectable;
 use log::debug;
 use serde::{Deserialize, Serialize};
-use std::{fmt::Display, path::Path};
+use std::{
+    fmt::Display,
+    io::IsTerminal,
+    path::{Path, PathBuf},
+};
 
-use crate::files::{pipewire_socket_path, read_file, settings_path, write_file};
+use crate::{
+    agent::KeyPolicy,
+    config::GlobalConfig,
+    env::{host_timezone, host_user_ids},
+    files::{
+        dbus_socket_path, host_gitconfig_path, host_known_hosts_path, pipewire_socket_path,
+        read_file, settings_path, write_file,
+    },
+    selinux::selinux_enabled,
+    utils::{assume_yes, ensure_interactive},
+};
+
+/// Direct Rendering Infrastructure devices used for GPU acceleration
+pub const DRI_DEVICE: &str = "/dev/dri";
+
+/// WSL2 DirectX device that takes the place of DRI under WSL
+pub const DXG_DEVICE: &str = "/dev/dxg";
+
+/// AMD Kernel Fusion Driver device used for GPU compute
+pub const KFD_DEVICE: &str = "/dev/kfd";
+
+/// Kernel-based Virtual Machine device used for hardware virtualisation
+pub const KVM_DEVICE: &str = "/dev/kvm";
 
 #[derive(Debug, Copy, Clone, Selectable, Serialize, Deserialize, PartialEq)]
 pub enum NetworkMode {
@@ -50,6 +76,56 @@ impl Display for NetworkMode {
     }
 }
 
+/// Terminal multiplexer that sessions get wrapped in, so that several
+/// terminals can share the same session inside a Litterbox
+#[derive(Debug, Copy, Clone, Selectable, Serialize, Deserialize, PartialEq)]
+pub enum Multiplexer {
+    None,
+    Tmux,
+    Screen,
+}
+
+impl Multiplexer {
+    fn name(&self) -> &'static str {
+        match self {
+            Multiplexer::None => "None (plain login shell)",
+            Multiplexer::Tmux => "tmux",
+            Multiplexer::Screen => "GNU Screen",
+        }
+    }
+
+    /// Command that attaches to the shared session, creating it the first time
+    pub fn command(&self) -> Option<&'static [&'static str]> {
+        match self {
+            Multiplexer::None => None,
+            Multiplexer::Tmux => Some(&["tmux", "new-session", "-A", "-s", "litterbox"]),
+            Multiplexer::Screen => Some(&["screen", "-xRR", "-S", "litterbox"]),
+        }
+    }
+}
+
+impl Display for Multiplexer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A host device attached to a Litterbox, either as a node created in the
+/// home directory or passed through when the container gets created
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct AttachedDevice {
+    /// Path of the device on the host, e.g. `/dev/ttyUSB0`
+    pub path: String,
+    /// Owner of the node as `uid:gid`
+    pub owner: String,
+    /// Permissions of the node as an octal mode, e.g. `0660`
+    pub perms: String,
+    /// Whether the device is passed to podman with `--device` instead of
+    /// getting a node in the home directory
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub passthrough: bool,
+}
+
 /// Settings for a Litterbox container, persisted to disk as RON.
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct LitterboxSettings {
@@ -63,8 +139,18 @@ pub struct LitterboxSettings {
     pub expose_pipewire: bool,
 
     // Settings added later which need defaults:
+    /// Whether the user's D-Bus session bus is mounted, e.g. for desktop
+    /// notifications and clipboard managers
+    #[serde(default = "default_false")]
+    pub expose_dbus: bool,
+    /// Passes `--group-add keep-groups` so that files owned by the host user's
+    /// supplementary groups stay accessible. The groups themselves do not show
+    /// up inside the container since host GIDs do not map under keep-id.
     #[serde(default = "default_false")]
     pub keep_groups: bool,
+    /// Whether the GPU (`/dev/dri`, or `/dev/dxg` under WSL) is exposed
+    #[serde(default = "default_true")]
+    pub expose_dri: bool,
     #[serde(default = "default_false")]
     pub expose_kfd: bool,
     #[serde(default = "default_false")]
@@ -75,16 +161,153 @@ pub struct LitterboxSettings {
     pub network_mode: NetworkMode,
     #[serde(default)]
     pub custom_podman_args: Option<String>,
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// Hostname inside the container instead of the `lbx-` prefixed default
+    #[serde(default)]
+    pub hostname: Option<String>,
+    /// Image (tag or digest) that the Dockerfile's `BASE_IMAGE` build-arg is
+    /// set to, so that rebuilds start from the same base
+    #[serde(default)]
+    pub base_image: Option<String>,
+    #[serde(default = "default_false")]
+    pub expose_kvm: bool,
+    /// Interactive Litterboxes stop when their last session ends whereas
+    /// non-interactive ones keep running so that they can host services.
+    #[serde(default = "default_true")]
+    pub interactive: bool,
+    /// Command to run when entering instead of the login shell
+    #[serde(default)]
+    pub start_command: Option<Vec<String>>,
+    /// Multiplexer to enter when there is no start command
+    #[serde(default = "default_multiplexer")]
+    pub multiplexer: Multiplexer,
+    /// Restricts which agent requests each attached key may be used for
+    #[serde(default)]
+    pub key_policy: KeyPolicy,
+    /// Whether the SSH agent asks for confirmation before handling requests
+    #[serde(default = "default_true")]
+    pub require_confirmation: bool,
+    /// Groups (names or GIDs from inside the container) that the user gets
+    /// added to with `--group-add`
+    #[serde(default)]
+    pub extra_groups: Vec<String>,
+    /// UID inside the container that the host user is mapped to with
+    /// `--userns=keep-id`, for images whose user does not share the host UID
+    #[serde(default)]
+    pub keep_id_uid: Option<u32>,
+    /// GID inside the container that the host user's group is mapped to
+    #[serde(default)]
+    pub keep_id_gid: Option<u32>,
+    /// Host script that runs inside the Litterbox once, right after its
+    /// container gets created
+    #[serde(default)]
+    pub post_create_hook: Option<PathBuf>,
+    /// Whether the host's `~/.gitconfig` is mounted read-only
+    #[serde(default = "default_false")]
+    pub share_gitconfig: bool,
+    /// Whether the host's `~/.ssh/known_hosts` is mounted read-only
+    #[serde(default = "default_false")]
+    pub share_known_hosts: bool,
+    /// Maximum number of processes inside the container
+    #[serde(default)]
+    pub pids_limit: Option<u32>,
+    /// Raw `--ulimit` entries, e.g. `nofile=1024:2048`
+    #[serde(default)]
+    pub ulimits: Vec<String>,
+    /// Whether the SSH agent approves signing requests without asking
+    #[serde(default = "default_false")]
+    pub auto_approve_signs: bool,
+    /// Limits automatic approvals, prompting again once it is exceeded
+    #[serde(default)]
+    pub auto_approve_signs_per_minute: Option<u32>,
+    /// Whether the SSH agent records its decisions in an audit log
+    #[serde(default = "default_false")]
+    pub audit_log: bool,
+    /// Whether the container gets labelled with an SELinux policy generated by
+    /// udica instead of having SELinux labelling disabled
+    #[serde(default = "default_false")]
+    pub selinux_policy: bool,
+    /// Whether the home directory is a host folder that outlives the container
+    #[serde(default = "default_true")]
+    pub mount_home: bool,
+    /// OCI annotations that the container gets created with, as KEY, VALUE
+    #[serde(default)]
+    pub annotations: Vec<(String, String)>,
+    /// Devices attached with `litterbox device`, so that they can be restored
+    #[serde(default)]
+    pub attached_devices: Vec<AttachedDevice>,
+}
+
+/// Parses the `UID:GID` that the host user should have inside the container
+fn parse_container_user(input: &str) -> Result<(u32, u32)> {
+    let (uid, gid) = input
+        .split_once(':')
+        .ok_or_else(|| anyhow!("\"{input}\" should be in the form UID:GID"))?;
+
+    match (uid.trim().parse(), gid.trim().parse()) {
+        (Ok(uid), Ok(gid)) => Ok((uid, gid)),
+        _ => bail!("\"{input}\" should use a numeric UID and GID"),
+    }
+}
+
+/// Validates a hostname against the rules of RFC 1123
+fn parse_hostname(input: &str) -> Result<String> {
+    let valid_label = |label: &str| {
+        (1..=63).contains(&label.len())
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    };
+
+    if input.len() > 253 || !input.split('.').all(valid_label) {
+        bail!(
+            "\"{input}\" is not a valid hostname. Use letters, digits and hyphens in \
+             dot-separated labels of at most 63 characters that do not start or end with a hyphen."
+        );
+    }
+
+    Ok(input.to_owned())
+}
+
+/// Validates a group for `--group-add`, which must be a GID or a group name
+fn parse_group(group: &str) -> Result<String> {
+    if group == "keep-groups" {
+        bail!("Use the keep_groups setting instead of adding \"keep-groups\" as a group");
+    }
+
+    let is_gid = group.parse::<u32>().is_ok();
+    let is_name = group
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+        && group
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-');
+
+    if !is_gid && !is_name {
+        bail!("Invalid group \"{group}\", expected a group name or numeric GID");
+    }
+
+    Ok(group.to_owned())
 }
 
 fn default_false() -> bool {
     false
 }
 
+fn default_true() -> bool {
+    true
+}
+
 fn default_pasta() -> NetworkMode {
     NetworkMode::Pasta
 }
 
+fn default_multiplexer() -> Multiplexer {
+    Multiplexer::None
+}
+
 impl LitterboxSettings {
     /// Load existing settings if available, prompt user if they want to change them,
     /// and save the final settings. This is the main entry point for getting settings
@@ -92,6 +315,17 @@ impl LitterboxSettings {
     pub fn load_or_prompt(lbx_name: &str) -> Result<Self> {
         let existing = Self::load(lbx_name)?;
 
+        if !std::io::stdin().is_terminal() {
+            // Re-use existing settings as is when nobody can be asked about them.
+            let path = settings_path(lbx_name)?;
+            return existing.ok_or_else(|| {
+                anyhow!(
+                    "Choosing the settings requires an interactive terminal, but STDIN is not a TTY.\n\
+                     Create the settings file at {path:?} first (e.g. by copying one from another Litterbox)."
+                )
+            });
+        }
+
         let settings = match &existing {
             Some(existing) => {
                 if Confirm::new("Would you like to change the settings for this Litterbox?")
@@ -110,7 +344,28 @@ impl LitterboxSettings {
         Ok(settings)
     }
 
-    fn load(lbx_name: &str) -> Result<Option<Self>> {
+    /// Asks about the existing settings again and saves the answers, without
+    /// touching the container. Changes apply once the container is recreated.
+    pub fn edit(lbx_name: &str) -> Result<Self> {
+        let existing = Self::require(lbx_name)?;
+        ensure_interactive(
+            "Editing the settings",
+            &format!("Edit {:?} directly instead.", settings_path(lbx_name)?),
+        )?;
+
+        let settings = Self::prompt(Some(&existing))?;
+        settings.save_to_file(lbx_name)?;
+        Ok(settings)
+    }
+
+    /// Loads the settings, failing if the Litterbox does not have any yet
+    pub fn require(lbx_name: &str) -> Result<Self> {
+        Self::load(lbx_name)?.ok_or_else(|| {
+            anyhow!("No settings exist for '{lbx_name}' yet. Build it to choose them.")
+        })
+    }
+
+    pub fn load(lbx_name: &str) -> Result<Option<Self>> {
         let path = settings_path(lbx_name)?;
         if !path.exists() {
             debug!("Settings file does not exist for {}", lbx_name);
@@ -122,18 +377,93 @@ impl LitterboxSettings {
         Ok(Some(settings))
     }
 
-    fn save_to_file(&self, lbx_name: &str) -> Result<()> {
+    /// The UID and GID that the host user has inside the container
+    pub fn container_user(&self) -> (u32, u32) {
+        let (host_uid, host_gid) = host_user_ids();
+        (
+            self.keep_id_uid.unwrap_or(host_uid),
+            self.keep_id_gid.unwrap_or(host_gid),
+        )
+    }
+
+    /// Remembers an attached device, replacing any earlier entry for it.
+    pub fn record_device(&mut self, lbx_name: &str, device: AttachedDevice) -> Result<()> {
+        self.attached_devices.retain(|d| d.path != device.path);
+        self.attached_devices.push(device);
+        self.save_to_file(lbx_name)
+    }
+
+    /// Sets annotations for future containers, replacing earlier values for
+    /// the same keys.
+    pub fn record_annotations(
+        &mut self,
+        lbx_name: &str,
+        annotations: &[(String, String)],
+    ) -> Result<()> {
+        for (key, value) in annotations {
+            self.annotations.retain(|(existing, _)| existing != key);
+            self.annotations.push((key.clone(), value.clone()));
+        }
+        self.save_to_file(lbx_name)
+    }
+
+    /// Pins the base image that future builds of the Litterbox start from.
+    pub fn record_base_image(&mut self, lbx_name: &str, base_image: &str) -> Result<()> {
+        self.base_image = Some(base_image.to_owned());
+        self.save_to_file(lbx_name)
+    }
+
+    /// A copy without the parts that only make sense on this host or reveal
+    /// which keys are attached, for sharing the definition with others
+    pub fn for_sharing(&self) -> Self {
+        Self {
+            key_policy: KeyPolicy::default(),
+            attached_devices: Vec::new(),
+            post_create_hook: None,
+            ..self.clone()
+        }
+    }
+
+    /// The settings as they are stored in the settings file
+    pub fn to_ron(&self) -> Result<String> {
         use ron::ser::{PrettyConfig, to_string_pretty};
 
+        to_string_pretty(self, PrettyConfig::default()).context("Failed to serialise settings")
+    }
+
+    pub fn save_to_file(&self, lbx_name: &str) -> Result<()> {
         let path = settings_path(lbx_name)?;
-        let contents = to_string_pretty(self, PrettyConfig::default())
-            .context("Failed to serialise settings")?;
-        write_file(&path, &contents)
+        write_file(&path, &self.to_ron()?)
     }
 
     fn prompt(existing: Option<&Self>) -> Result<Self> {
-        let network_mode = NetworkMode::select("Choose the network mode for this Litterbox:")
-            .with_starting_cursor(existing.map(|s| s.network_mode as usize).unwrap_or(0))
+        // New Litterboxes start out with the globally preferred network mode
+        let default_network_mode = match existing {
+            Some(existing) => Some(existing.network_mode),
+            None => GlobalConfig::load()?.default_network_mode,
+        };
+        let network_mode = match default_network_mode {
+            Some(mode) if existing.is_none() && assume_yes() => {
+                eprintln!("Using the default network mode: {mode}");
+                mode
+            }
+            _ => NetworkMode::select("Choose the network mode for this Litterbox:")
+                .with_starting_cursor(default_network_mode.map(|m| m as usize).unwrap_or(0))
+                .prompt()?,
+        };
+
+        let interactive = Confirm::new("Should this Litterbox stop when its last session exits?")
+            .with_default(existing.map(|s| s.interactive).unwrap_or(true))
+            .with_help_message(
+                "Choose no for a service-style Litterbox that keeps running (e.g. a dev server).",
+            )
+            .prompt()?;
+
+        let mount_home = Confirm::new("Should the home directory be stored on the host?")
+            .with_default(existing.map(|s| s.mount_home).unwrap_or(true))
+            .with_help_message(
+                "Choose no for a fully ephemeral Litterbox whose files vanish when it is deleted.",
+            )
             .prompt()?;
 
         let support_ping = Confirm::new("Do you want to support `ping` inside this Litterbox?")
@@ -155,8 +485,43 @@ impl LitterboxSettings {
         let keep_groups =
             Confirm::new("Do you want to keep your user groups inside this Litterbox?")
                 .with_default(existing.map(|s| s.keep_groups).unwrap_or(false))
-                .with_help_message("This will preserve your host user's group memberships.")
+                .with_help_message(
+                    "Files owned by your host groups stay accessible, but the groups are not visible inside.",
+                )
+                .prompt()?;
+
+        let extra_groups_input =
+            Text::new("Additional groups for the user (space-separated, leave empty for none):")
+                .with_default(
+                    &existing
+                        .map(|s| s.extra_groups.join(" "))
+                        .unwrap_or_default(),
+                )
+                .with_help_message("Names or GIDs of groups inside the container (e.g., video 44).")
                 .prompt()?;
+        let extra_groups = extra_groups_input
+            .split_whitespace()
+            .map(parse_group)
+            .collect::<Result<Vec<_>>>()?;
+
+        let container_user_default = existing
+            .and_then(|s| s.keep_id_uid.zip(s.keep_id_gid))
+            .map(|(uid, gid)| format!("{uid}:{gid}"))
+            .unwrap_or_default();
+        let container_user_input = Text::new(
+            "UID:GID of the user inside the container (leave empty to use yours):",
+        )
+        .with_default(&container_user_default)
+        .with_help_message(
+            "Only needed when the Dockerfile creates its user with a fixed UID (e.g., 1000:1000).",
+        )
+        .prompt()?;
+        let (keep_id_uid, keep_id_gid) = if container_user_input.trim().is_empty() {
+            (None, None)
+        } else {
+            let (uid, gid) = parse_container_user(container_user_input.trim())?;
+            (Some(uid), Some(gid))
+        };
 
         let unconfine_seccomp = Confirm::new("Do you want to disable seccomp confinement?")
             .with_default(existing.map(|s| s.unconfine_seccomp).unwrap_or(false))
@@ -165,13 +530,37 @@ impl LitterboxSettings {
             )
             .prompt()?;
 
-        let expose_kfd = if Path::new("/dev/kfd").exists() {
-            Confirm::new("Do you want to expose /dev/kfd inside this Litterbox?")
-                .with_default(existing.map(|s| s.expose_kfd).unwrap_or(false))
-                .with_help_message("This will expose the AMD Kernel Fusion Driver for GPU compute.")
+        let expose_dri = if Path::new(DRI_DEVICE).exists() || Path::new(DXG_DEVICE).exists() {
+            Confirm::new("Do you want to expose the GPU inside this Litterbox?")
+                .with_default(existing.map(|s| s.expose_dri).unwrap_or(true))
+                .with_help_message("This is needed for GPU accelerated graphics.")
                 .prompt()?
         } else {
-            debug!("/dev/kfd not found on host system, user not prompted to expose it.");
+            debug!("No GPU device found on host system, user not prompted to expose it.");
+            existing.map(|s| s.expose_dri).unwrap_or(true)
+        };
+
+        let expose_kfd = if Path::new(KFD_DEVICE).exists() {
+            Confirm::new(&format!(
+                "Do you want to expose {KFD_DEVICE} inside this Litterbox?"
+            ))
+            .with_default(existing.map(|s| s.expose_kfd).unwrap_or(false))
+            .with_help_message("This will expose the AMD Kernel Fusion Driver for GPU compute.")
+            .prompt()?
+        } else {
+            debug!("{KFD_DEVICE} not found on host system, user not prompted to expose it.");
+            false
+        };
+
+        let expose_kvm = if Path::new(KVM_DEVICE).exists() {
+            Confirm::new(&format!(
+                "Do you want to expose {KVM_DEVICE} inside this Litterbox?"
+            ))
+            .with_default(existing.map(|s| s.expose_kvm).unwrap_or(false))
+            .with_help_message("This will allow virtual machines to use hardware acceleration.")
+            .prompt()?
+        } else {
+            debug!("{KVM_DEVICE} not found on host system, user not prompted to expose it.");
             false
         };
 
@@ -187,6 +576,91 @@ impl LitterboxSettings {
             false
         };
 
+        let expose_dbus = if dbus_socket_path()?.exists() {
+            Confirm::new("Do you want to expose the D-Bus session bus inside this Litterbox?")
+                .with_default(existing.map(|s| s.expose_dbus).unwrap_or(false))
+                .with_help_message(
+                    "This lets apps send notifications, but also talk to any other service on the bus.",
+                )
+                .prompt()?
+        } else {
+            debug!("D-Bus socket not found on host system, user not prompted to expose it.");
+            false
+        };
+
+        let selinux_policy = if selinux_enabled() {
+            Confirm::new("Do you want to generate an SELinux policy for this Litterbox?")
+                .with_default(existing.map(|s| s.selinux_policy).unwrap_or(false))
+                .with_help_message(
+                    "Instead of disabling SELinux labelling, udica generates a policy from the mounts. Requires root.",
+                )
+                .prompt()?
+        } else {
+            debug!("SELinux is not enabled on the host system, user not prompted to use a policy.");
+            false
+        };
+
+        let share_gitconfig = if host_gitconfig_path()?.exists() {
+            Confirm::new("Do you want to share your git config with this Litterbox?")
+                .with_default(existing.map(|s| s.share_gitconfig).unwrap_or(false))
+                .with_help_message("This will mount ~/.gitconfig read-only.")
+                .prompt()?
+        } else {
+            debug!("~/.gitconfig not found on host system, user not prompted to share it.");
+            false
+        };
+
+        let share_known_hosts = if host_known_hosts_path()?.exists() {
+            Confirm::new("Do you want to share your SSH known hosts with this Litterbox?")
+                .with_default(existing.map(|s| s.share_known_hosts).unwrap_or(false))
+                .with_help_message("This will mount ~/.ssh/known_hosts read-only.")
+                .prompt()?
+        } else {
+            debug!("~/.ssh/known_hosts not found on host system, user not prompted to share it.");
+            false
+        };
+
+        let require_confirmation = Confirm::new(
+            "Do you want to confirm each SSH agent request from this Litterbox?",
+        )
+        .with_default(existing.map(|s| s.require_confirmation).unwrap_or(true))
+        .with_help_message(
+            "Without confirmation, anything inside the Litterbox can use the attached keys freely.",
+        )
+        .prompt()?;
+
+        let auto_approve_signs = require_confirmation
+            && Confirm::new("Do you want to approve signing requests automatically?")
+                .with_default(existing.map(|s| s.auto_approve_signs).unwrap_or(false))
+                .with_help_message("Other requests will still need to be confirmed.")
+                .prompt()?;
+
+        let auto_approve_signs_per_minute = if auto_approve_signs {
+            let limit_default = existing.and_then(|s| s.auto_approve_signs_per_minute);
+            let limit_input =
+                Text::new("Maximum automatic approvals per minute (leave empty for no limit):")
+                    .with_default(&limit_default.map(|v| v.to_string()).unwrap_or_default())
+                    .with_help_message("You will be prompted for any signing requests beyond this.")
+                    .prompt()?;
+
+            if limit_input.trim().is_empty() {
+                None
+            } else {
+                Some(limit_input.trim().parse().map_err(|_| {
+                    anyhow!("auto_approve_signs_per_minute must be a valid integer")
+                })?)
+            }
+        } else {
+            None
+        };
+
+        let audit_log = Confirm::new("Do you want to keep an audit log of SSH agent requests?")
+            .with_default(existing.map(|s| s.audit_log).unwrap_or(false))
+            .with_help_message(
+                "Requests and decisions get appended to ~/Litterbox/.ssh/NAME.audit.jsonl.",
+            )
+            .prompt()?;
+
         let shm_size_default = existing.and_then(|s| s.shm_size_gb);
         let shm_size_input = Text::new("Shared memory size in GB (leave empty for default):")
             .with_default(&shm_size_default.map(|v| v.to_string()).unwrap_or_default())
@@ -203,6 +677,36 @@ impl LitterboxSettings {
             )
         };
 
+        let pids_limit_default = existing.and_then(|s| s.pids_limit);
+        let pids_limit_input = Text::new("Maximum number of processes (leave empty for default):")
+            .with_default(
+                &pids_limit_default
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            )
+            .with_help_message("Sets --pids-limit for the container to guard against fork bombs.")
+            .prompt()?;
+        let pids_limit: Option<u32> = if pids_limit_input.trim().is_empty() {
+            None
+        } else {
+            match pids_limit_input.trim().parse() {
+                Ok(0) | Err(_) => bail!("pids_limit must be a positive integer"),
+                Ok(limit) => Some(limit),
+            }
+        };
+
+        let ulimits_input = Text::new("Ulimits (space-separated, leave empty for none):")
+            .with_default(&existing.map(|s| s.ulimits.join(" ")).unwrap_or_default())
+            .with_help_message("Each entry is passed to --ulimit (e.g., nofile=1024:2048).")
+            .prompt()?;
+        let ulimits: Vec<String> = ulimits_input
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+        if let Some(invalid) = ulimits.iter().find(|u| !u.contains('=')) {
+            bail!("Invalid ulimit \"{invalid}\", expected TYPE=SOFT[:HARD]");
+        }
+
         let custom_podman_args_input =
             Text::new("Custom podman arguments (space-separated, leave empty for none):")
                 .with_default(
@@ -218,18 +722,192 @@ impl LitterboxSettings {
             Some(custom_podman_args_input.trim().to_string())
         };
 
+        let timezone_default = existing
+            .and_then(|s| s.timezone.clone())
+            .or_else(host_timezone)
+            .unwrap_or_default();
+        let timezone_input = Text::new("Timezone for this Litterbox (leave empty for default):")
+            .with_default(&timezone_default)
+            .with_help_message("Sets TZ inside the container (e.g., Europe/Amsterdam).")
+            .prompt()?;
+        let timezone: Option<String> = if timezone_input.trim().is_empty() {
+            None
+        } else {
+            Some(timezone_input.trim().to_string())
+        };
+
+        let hostname_input = Text::new("Hostname for this Litterbox (leave empty for default):")
+            .with_default(
+                existing
+                    .and_then(|s| s.hostname.as_deref())
+                    .unwrap_or_default(),
+            )
+            .with_help_message("Defaults to the name of the Litterbox prefixed with lbx-.")
+            .prompt()?;
+        let hostname: Option<String> = if hostname_input.trim().is_empty() {
+            None
+        } else {
+            Some(parse_hostname(hostname_input.trim())?)
+        };
+
+        let start_command_input =
+            Text::new("Command to run when entering (leave empty for the login shell):")
+                .with_default(
+                    &existing
+                        .and_then(|s| s.start_command.as_ref())
+                        .map(|c| c.join(" "))
+                        .unwrap_or_default(),
+                )
+                .with_help_message("Example: tmux new-session -A -s main")
+                .prompt()?;
+        let start_command: Option<Vec<String>> = if start_command_input.trim().is_empty() {
+            None
+        } else {
+            Some(
+                start_command_input
+                    .split_whitespace()
+                    .map(str::to_string)
+                    .collect(),
+            )
+        };
+
+        let multiplexer = Multiplexer::select("Choose a multiplexer to wrap sessions in:")
+            .with_starting_cursor(existing.map(|s| s.multiplexer as usize).unwrap_or(0))
+            .with_help_message(
+                "Lets several terminals share one session. It must be installed inside the Litterbox.",
+            )
+            .prompt()?;
+
+        let post_create_hook_input =
+            Text::new("Script to run once after creating the Litterbox (leave empty for none):")
+                .with_default(
+                    &existing
+                        .and_then(|s| s.post_create_hook.as_ref())
+                        .map(|p| p.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                )
+                .with_help_message("A host script that gets run inside, e.g. to set up git config.")
+                .prompt()?;
+        let post_create_hook: Option<PathBuf> = if post_create_hook_input.trim().is_empty() {
+            None
+        } else {
+            let path = PathBuf::from(post_create_hook_input.trim());
+            if !path.is_file() {
+                bail!("Post-create hook {path:?} does not exist");
+            }
+            Some(path)
+        };
+
         Ok(Self {
             version: 1,
+            interactive,
+            mount_home,
             network_mode,
             support_ping,
             support_tuntap,
             packet_forwarding,
             unconfine_seccomp,
             expose_pipewire,
+            expose_dbus,
+            share_gitconfig,
+            share_known_hosts,
             keep_groups,
+            extra_groups,
+            keep_id_uid,
+            keep_id_gid,
+            expose_dri,
             expose_kfd,
+            expose_kvm,
             shm_size_gb,
+            pids_limit,
+            ulimits,
             custom_podman_args,
+            timezone,
+            hostname,
+            base_image: existing.and_then(|s| s.base_image.clone()),
+            start_command,
+            multiplexer,
+            post_create_hook,
+            require_confirmation,
+            auto_approve_signs,
+            auto_approve_signs_per_minute,
+            audit_log,
+            selinux_policy,
+            key_policy: existing.map(|s| s.key_policy.clone()).unwrap_or_default(),
+            annotations: existing.map(|s| s.annotations.clone()).unwrap_or_default(),
+            attached_devices: existing
+                .map(|s| s.attached_devices.clone())
+                .unwrap_or_default(),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_gates_use_correct_paths() {
+        assert_eq!(KFD_DEVICE, "/dev/kfd");
+        assert_eq!(KVM_DEVICE, "/dev/kvm");
+        assert_eq!(DRI_DEVICE, "/dev/dri");
+        assert_eq!(DXG_DEVICE, "/dev/dxg");
+    }
+
+    #[test]
+    fn validates_groups() {
+        assert_eq!(parse_group("video").unwrap(), "video");
+        assert_eq!(parse_group("44").unwrap(), "44");
+        assert!(parse_group("keep-groups").is_err());
+        assert!(parse_group("wheel,root").is_err());
+        assert!(parse_group("-1").is_err());
+    }
+
+    #[test]
+    fn validates_hostnames() {
+        assert_eq!(parse_hostname("devbox").unwrap(), "devbox");
+        assert_eq!(parse_hostname("Dev-1.local").unwrap(), "Dev-1.local");
+        assert!(parse_hostname("").is_err());
+        assert!(parse_hostname("-devbox").is_err());
+        assert!(parse_hostname("devbox-").is_err());
+        assert!(parse_hostname("dev_box").is_err());
+        assert!(parse_hostname("dev..box").is_err());
+        assert!(parse_hostname(&"a".repeat(64)).is_err());
+    }
+
+    #[test]
+    fn parses_container_user() {
+        assert_eq!(parse_container_user("1000:1000").unwrap(), (1000, 1000));
+        assert_eq!(parse_container_user("1001:100").unwrap(), (1001, 100));
+        assert!(parse_container_user("1000").is_err());
+        assert!(parse_container_user("user:users").is_err());
+        assert!(parse_container_user("-1:0").is_err());
+    }
+
+    #[test]
+    fn old_settings_default_new_fields() {
+        let settings: LitterboxSettings = ron::from_str(
+            "(version: 1, support_ping: true, support_tuntap: false, \
+             packet_forwarding: false, expose_pipewire: false)",
+        )
+        .unwrap();
+
+        assert!(settings.support_ping);
+        assert!(settings.expose_dri);
+        assert!(!settings.expose_dbus);
+        assert!(!settings.expose_kfd);
+        assert!(!settings.expose_kvm);
+        assert!(settings.interactive);
+        assert!(settings.require_confirmation);
+        assert!(settings.mount_home);
+        assert!(!settings.audit_log);
+        assert!(!settings.selinux_policy);
+        assert!(settings.attached_devices.is_empty());
+        assert!(settings.annotations.is_empty());
+        assert_eq!(settings.base_image, None);
+        assert_eq!(settings.hostname, None);
+        assert_eq!(settings.container_user(), host_user_ids());
+        assert_eq!(settings.network_mode, NetworkMode::Pasta);
+        assert_eq!(settings.multiplexer, Multiplexer::None);
+    }
+}
@@ -1,10 +1,18 @@
+use anyhow::{Context, Result, anyhow};
+use clap::ValueEnum;
 use inquire_derive::Selectable;
 use std::fmt::Display;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Copy, Clone, Selectable)]
+use crate::files::{custom_templates_path, read_file};
+
+#[derive(Debug, Copy, Clone, Selectable, ValueEnum)]
 pub enum Template {
+    #[value(name = "tumbleweed")]
     OpenSuseTumbleweed,
+    #[value(name = "ubuntu-lts")]
     UbuntuLts,
+    #[value(name = "cachyos")]
     CachyOS,
 }
 
@@ -17,6 +25,14 @@ impl Template {
         }
     }
 
+    /// The name that selects the template on the command line
+    pub fn cli_name(&self) -> String {
+        self.to_possible_value()
+            .expect("Templates should not be skipped")
+            .get_name()
+            .to_owned()
+    }
+
     pub fn name(&self) -> &'static str {
         match self {
             Template::OpenSuseTumbleweed => "OpenSUSE Tumbleweed",
@@ -31,3 +47,192 @@ impl Display for Template {
         f.write_str(self.name())
     }
 }
+
+/// A template Dockerfile from `~/Litterbox/templates`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomTemplate {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// A template that is not usable as a Dockerfile
+#[derive(Debug)]
+pub struct InvalidTemplate {
+    pub name: String,
+    pub reason: &'static str,
+}
+
+impl Display for InvalidTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Template '{}' is not a valid Dockerfile: {}",
+            self.name, self.reason
+        )
+    }
+}
+
+impl std::error::Error for InvalidTemplate {}
+
+/// Catches templates that could only fail once they get built
+fn validate(name: &str, contents: &str) -> Result<(), InvalidTemplate> {
+    let invalid = |reason| InvalidTemplate {
+        name: name.to_owned(),
+        reason,
+    };
+
+    if contents.trim().is_empty() {
+        return Err(invalid("it is empty"));
+    }
+
+    let has_from = contents.lines().any(|line| {
+        let line = line.trim_start();
+        line.get(..5)
+            .is_some_and(|start| start.eq_ignore_ascii_case("FROM "))
+    });
+    if !has_from {
+        return Err(invalid("it has no FROM line"));
+    }
+
+    Ok(())
+}
+
+/// A built-in or custom template as offered to the user
+pub struct TemplateEntry {
+    pub name: String,
+    pub description: String,
+}
+
+impl Display for TemplateEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.description)
+    }
+}
+
+/// All templates, the built-in ones first
+pub fn all_templates() -> Result<Vec<TemplateEntry>> {
+    let mut templates: Vec<TemplateEntry> = Template::value_variants()
+        .iter()
+        .map(|template| TemplateEntry {
+            name: template.cli_name(),
+            description: template.name().to_owned(),
+        })
+        .collect();
+
+    for template in custom_templates()? {
+        templates.push(TemplateEntry {
+            name: template.name,
+            description: format!("Custom ({})", template.path.display()),
+        });
+    }
+
+    Ok(templates)
+}
+
+/// Finds the `NAME.Dockerfile` templates in `dir`, sorted by name
+fn custom_templates_in(dir: &Path) -> Result<Vec<CustomTemplate>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut templates = Vec::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("Could not list {dir:?}"))? {
+        let path = entry?.path();
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(".Dockerfile"));
+
+        if let Some(name) = name {
+            templates.push(CustomTemplate {
+                name: name.to_owned(),
+                path: path.clone(),
+            });
+        }
+    }
+
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(templates)
+}
+
+pub fn custom_templates() -> Result<Vec<CustomTemplate>> {
+    custom_templates_in(&custom_templates_path()?)
+}
+
+/// Looks up the contents of a built-in template by its command line name or
+/// otherwise of a custom one, making sure that they can be used as a Dockerfile
+fn template_contents_in(name: &str, dir: &Path) -> Result<String> {
+    if let Ok(template) = Template::from_str(name, true) {
+        return Ok(template.contents().to_owned());
+    }
+
+    let custom = custom_templates_in(dir)?
+        .into_iter()
+        .find(|template| template.name == name)
+        .ok_or_else(|| {
+            anyhow!("No template named '{name}'. Run `litterbox template list` to see them all.")
+        })?;
+
+    let contents = read_file(&custom.path)?;
+    validate(name, &contents)?;
+    Ok(contents)
+}
+
+pub fn template_contents(name: &str) -> Result<String> {
+    template_contents_in(name, &custom_templates_path()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_templates() {
+        for template in Template::value_variants() {
+            assert!(validate(template.name(), template.contents()).is_ok());
+        }
+        assert!(validate("ok", "# comment\n  from alpine AS base\n").is_ok());
+        assert_eq!(validate("empty", " \n").unwrap_err().reason, "it is empty");
+        assert_eq!(
+            validate("no-from", "RUN echo hi\nFROMAGE\n")
+                .unwrap_err()
+                .reason,
+            "it has no FROM line"
+        );
+    }
+
+    #[test]
+    fn finds_built_in_and_custom_templates() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("rust.Dockerfile"), "FROM rust\n").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "").unwrap();
+
+        let templates = custom_templates_in(dir.path()).unwrap();
+        assert_eq!(
+            templates,
+            [CustomTemplate {
+                name: "rust".to_owned(),
+                path: dir.path().join("rust.Dockerfile"),
+            }]
+        );
+
+        assert_eq!(
+            template_contents_in("rust", dir.path()).unwrap(),
+            "FROM rust\n"
+        );
+        assert_eq!(
+            template_contents_in("ubuntu-lts", dir.path()).unwrap(),
+            Template::UbuntuLts.contents()
+        );
+        assert!(template_contents_in("notes", dir.path()).is_err());
+
+        std::fs::write(dir.path().join("empty.Dockerfile"), "\n").unwrap();
+        let err = template_contents_in("empty", dir.path()).unwrap_err();
+        assert!(err.is::<InvalidTemplate>(), "{err}");
+        assert!(
+            custom_templates_in(&dir.path().join("missing"))
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use anyhow::{Context, Result, anyhow, bail};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::{
+    process::Command,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    config::GlobalConfig,
+    files::{read_file, update_check_path, write_file},
+    utils::{extract_stdout, trace_arguments},
+};
+
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/Gerharddc/litterbox/releases/latest";
+
+/// How long the latest version is remembered before asking GitHub again
+const CACHE_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long to wait after a failed check (e.g. when offline) before trying
+/// again, so that commands do not keep waiting for the request to time out
+const FAILED_CACHE_DURATION: Duration = Duration::from_secs(60 * 60);
+
+/// The outcome of the last check, cached at `~/Litterbox/.update-check.ron`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+struct UpdateCheck {
+    /// Seconds since the Unix epoch
+    checked_at: u64,
+    /// `None` when the check failed
+    latest_version: Option<String>,
+}
+
+impl UpdateCheck {
+    fn is_fresh(&self, now: u64) -> bool {
+        let duration = match self.latest_version {
+            Some(_) => CACHE_DURATION,
+            None => FAILED_CACHE_DURATION,
+        };
+        now.saturating_sub(self.checked_at) < duration.as_secs()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Parses a version such as `v0.5.0` into its numeric parts, ignoring any
+/// pre-release or build suffix
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.trim().trim_start_matches('v');
+    let version = version.split(['-', '+']).next()?;
+
+    let mut parts = version.split('.').map(|part| part.parse().ok());
+    let major = parts.next()??;
+    let minor = parts.next().unwrap_or(Some(0))?;
+    let patch = parts.next().unwrap_or(Some(0))?;
+    Some((major, minor, patch))
+}
+
+fn is_newer(latest: &str, current: &str) -> bool {
+    match (parse_version(latest), parse_version(current)) {
+        (Some(latest), Some(current)) => latest > current,
+        _ => false,
+    }
+}
+
+fn fetch_latest_version() -> Result<String> {
+    let mut cmd = Command::new("curl");
+    cmd.args([
+        "--fail",
+        "--silent",
+        "--show-error",
+        "--location",
+        "--max-time",
+        "10",
+        "--header",
+        "Accept: application/vnd.github+json",
+        LATEST_RELEASE_URL,
+    ]);
+
+    let span = trace_arguments(&cmd);
+    let output = cmd
+        .output()
+        .context("Failed to run curl, is it installed?")?;
+    drop(span);
+    let release: Release = serde_json::from_str(extract_stdout(&output)?)
+        .context("Failed to parse the latest release from GitHub")?;
+
+    Ok(release.tag_name.trim_start_matches('v').to_owned())
+}
+
+fn load_cached() -> Option<UpdateCheck> {
+    let path = update_check_path().ok()?;
+    if !path.exists() {
+        return None;
+    }
+
+    match read_file(&path).and_then(|contents| Ok(ron::from_str(&contents)?)) {
+        Ok(check) => Some(check),
+        Err(e) => {
+            debug!("Ignoring the cached update check: {e:#}");
+            None
+        }
+    }
+}
+
+/// Looks up the latest released version, asking GitHub at most once a day
+/// (or once an hour after a failed check) unless `refresh` is set
+pub fn latest_version(refresh: bool) -> Result<String> {
+    let now = now();
+    if !refresh && let Some(check) = load_cached().filter(|check| check.is_fresh(now)) {
+        debug!("Using the update check from {}", check.checked_at);
+        return check.latest_version.ok_or_else(|| {
+            anyhow!("The last check for updates failed recently. Pass --refresh to try again.")
+        });
+    }
+
+    let fetched = fetch_latest_version();
+    let check = UpdateCheck {
+        checked_at: now,
+        latest_version: fetched.as_ref().ok().cloned(),
+    };
+    let contents = ron::ser::to_string(&check)?;
+    write_file(&update_check_path()?, &contents)?;
+
+    fetched
+}
+
+/// The latest version if it is newer than this build of Litterbox
+pub fn available_update(refresh: bool) -> Result<Option<String>> {
+    let latest = latest_version(refresh)?;
+    if parse_version(&latest).is_none() {
+        bail!("GitHub reported an unexpected version \"{latest}\"");
+    }
+
+    Ok(is_newer(&latest, env!("CARGO_PKG_VERSION")).then_some(latest))
+}
+
+/// Mentions a newer version on STDERR when enabled in the global config.
+/// Failures are only logged since they should never get in the way.
+pub fn notify_if_enabled() {
+    let enabled = match GlobalConfig::load() {
+        Ok(config) => config.check_for_updates,
+        Err(e) => {
+            debug!("Not checking for updates: {e:#}");
+            false
+        }
+    };
+    if !enabled {
+        return;
+    }
+
+    match available_update(false) {
+        Ok(Some(latest)) => eprintln!(
+            "Litterbox {latest} is available (you have {}).",
+            env!("CARGO_PKG_VERSION")
+        ),
+        Ok(None) => {}
+        Err(e) => debug!("Could not check for updates: {e:#}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_versions() {
+        assert_eq!(parse_version("v0.5.0"), Some((0, 5, 0)));
+        assert_eq!(parse_version("1.2"), Some((1, 2, 0)));
+        assert_eq!(parse_version("1.2.3-rc.1"), Some((1, 2, 3)));
+        assert_eq!(parse_version("latest"), None);
+
+        assert!(is_newer("0.10.0", "0.9.1"));
+        assert!(is_newer("v1.0.0", "0.5.0"));
+        assert!(!is_newer("0.5.0", "0.5.0"));
+        assert!(!is_newer("0.4.9", "0.5.0"));
+        assert!(!is_newer("nightly", "0.5.0"));
+    }
+
+    #[test]
+    fn caches_checks_for_a_day() {
+        let check = UpdateCheck {
+            checked_at: 1_000_000,
+            latest_version: Some("0.5.0".to_owned()),
+        };
+
+        assert!(check.is_fresh(1_000_000));
+        assert!(check.is_fresh(1_000_000 + 23 * 60 * 60));
+        assert!(!check.is_fresh(1_000_000 + 24 * 60 * 60));
+
+        let failed = UpdateCheck {
+            checked_at: 1_000_000,
+            latest_version: None,
+        };
+        assert!(failed.is_fresh(1_000_000 + 59 * 60));
+        assert!(!failed.is_fresh(1_000_000 + 60 * 60));
+
+        let parsed: UpdateCheck = ron::from_str(&ron::ser::to_string(&check).unwrap()).unwrap();
+        assert_eq!(parsed, check);
+    }
+}
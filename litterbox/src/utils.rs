@@ -1,17 +1,64 @@
-use anyhow::{Result, bail};
-use log::trace;
-use std::process::{Command, Output};
-
-pub fn trace_arguments(cmd: &Command) {
-    trace!(
-        "Will run: {} {}",
-        cmd.get_program().to_string_lossy(),
-        cmd.get_args().fold(String::new(), |mut acc, arg| {
-            acc.push_str(&arg.to_string_lossy());
-            acc.push(' ');
-            acc
-        })
-    );
+use anyhow::{Result, bail, ensure};
+use log::{trace, warn};
+use tracing::span::EnteredSpan;
+use tracing_subscriber::fmt::format::FmtSpan;
+
+use crate::{
+    env::{self, WaylandSocketMissing},
+    files::{HomeCopyError, SocketInUse},
+    keys::CorruptKeyfile,
+    output::{self, ErrorReport},
+    podman::PostCreateHookError,
+    template::InvalidTemplate,
+};
+use std::{
+    io::IsTerminal,
+    process::{Command, Output},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Set from the global `--yes` argument
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+pub fn set_assume_yes(yes: bool) {
+    ASSUME_YES.store(yes, Ordering::SeqCst);
+}
+
+/// Whether confirmation prompts should be skipped as if the user said yes
+pub fn assume_yes() -> bool {
+    ASSUME_YES.load(Ordering::SeqCst)
+}
+
+/// Prints how long each span took to STDERR once it closes, when enabled
+/// with `--trace` or `LITTERBOX_TRACE`. Without a subscriber the spans cost
+/// next to nothing.
+pub fn init_span_timing() {
+    if !env::trace_enabled() {
+        return;
+    }
+
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_target(false)
+        .finish();
+    if let Err(e) = tracing::subscriber::set_global_default(subscriber) {
+        warn!("Could not enable span timing: {e}");
+    }
+}
+
+/// Logs the command that is about to run. The returned span times it for as
+/// long as it is held.
+pub fn trace_arguments(cmd: &Command) -> EnteredSpan {
+    let program = cmd.get_program().to_string_lossy();
+    let args = cmd.get_args().fold(String::new(), |mut acc, arg| {
+        acc.push_str(&arg.to_string_lossy());
+        acc.push(' ');
+        acc
+    });
+    trace!("Will run: {program} {args}");
+
+    tracing::info_span!("command", %program, args = args.trim_end()).entered()
 }
 
 pub fn extract_stdout(output: &Output) -> Result<&str> {
@@ -27,3 +74,259 @@ pub fn extract_stdout(output: &Output) -> Result<&str> {
 pub fn podman_name(lbx_name: &str) -> String {
     format!("lbx-{lbx_name}")
 }
+
+/// Fails with actionable guidance when there is no terminal to prompt on,
+/// instead of letting `inquire` fail with an opaque error.
+pub fn ensure_interactive(action: &str, guidance: &str) -> Result<()> {
+    ensure!(
+        std::io::stdin().is_terminal(),
+        "{action} requires an interactive terminal, but STDIN is not a TTY.\n{guidance}"
+    );
+
+    Ok(())
+}
+
+/// Describes an error as a JSON object with its kind, message and the causes
+/// behind it, for tools that wrap Litterbox.
+pub fn error_to_json(e: &anyhow::Error) -> serde_json::Value {
+    let kind = e
+        .chain()
+        .find_map(|cause| {
+            if cause.is::<WaylandSocketMissing>() {
+                Some("wayland_socket_missing")
+            } else if cause.is::<PostCreateHookError>() {
+                Some("post_create_hook_failed")
+            } else if cause.is::<SocketInUse>() {
+                Some("socket_in_use")
+            } else if cause.is::<CorruptKeyfile>() {
+                Some("corrupt_keyfile")
+            } else if cause.is::<HomeCopyError>() {
+                Some("home_copy_failed")
+            } else if cause.is::<InvalidTemplate>() {
+                Some("invalid_template")
+            } else if cause.is::<inquire::InquireError>() {
+                Some("prompt_failed")
+            } else if cause.is::<std::io::Error>() {
+                Some("io")
+            } else {
+                None
+            }
+        })
+        .unwrap_or("error");
+
+    let causes: Vec<String> = e.chain().skip(1).map(ToString::to_string).collect();
+
+    output::to_json(&ErrorReport {
+        error: kind,
+        message: e.to_string(),
+        detail: (!causes.is_empty()).then(|| causes.join(": ")),
+    })
+}
+
+/// Parses a duration such as `30d`, made up of a number and one of the units
+/// s, m, h, d or w
+pub fn parse_duration(input: &str) -> Result<std::time::Duration> {
+    let unit_start = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(unit_start);
+
+    let seconds_per_unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => bail!("Duration \"{input}\" should end in one of the units s, m, h, d or w"),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Duration \"{input}\" should start with a number"))?;
+
+    let seconds = number
+        .checked_mul(seconds_per_unit)
+        .ok_or_else(|| anyhow::anyhow!("Duration \"{input}\" is too long"))?;
+
+    Ok(std::time::Duration::from_secs(seconds))
+}
+
+/// Formats a number of bytes in the largest binary unit that fits, e.g. "1.5 GiB"
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = UNITS[0];
+    for next_unit in &UNITS[1..] {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next_unit;
+    }
+
+    format!("{size:.1} {unit}")
+}
+
+/// Formats a UNIX timestamp as a rough age such as "3 days ago"
+pub fn format_age(timestamp: i64) -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+    let seconds = (now - timestamp).max(0);
+
+    let (amount, unit) = match seconds {
+        s if s < 60 => return "just now".to_owned(),
+        s if s < 60 * 60 => (s / 60, "minute"),
+        s if s < 60 * 60 * 24 => (s / (60 * 60), "hour"),
+        s => (s / (60 * 60 * 24), "day"),
+    };
+
+    format!("{amount} {unit}{} ago", if amount == 1 { "" } else { "s" })
+}
+
+/// Matches a name against a simple glob pattern where `*` matches any number
+/// of characters and `?` matches a single character.
+pub fn glob_matches(pattern: &str, name: &str) -> bool {
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match pattern.split_first() {
+            None => name.is_empty(),
+            Some(('*', rest)) => (0..=name.len()).any(|i| matches(rest, &name[i..])),
+            Some(('?', rest)) => !name.is_empty() && matches(rest, &name[1..]),
+            Some((c, rest)) => name.first() == Some(c) && matches(rest, &name[1..]),
+        }
+    }
+
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    matches(&pattern, &name)
+}
+
+/// Number of single character edits needed to turn `a` into `b`
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Finds the candidate closest to `name`, as long as it is reasonably close
+pub fn closest_match<'a>(name: &str, candidates: &'a [String]) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(2);
+
+    candidates
+        .iter()
+        .map(|candidate| (levenshtein(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_matching() {
+        assert!(glob_matches("foo", "foo"));
+        assert!(!glob_matches("foo", "foobar"));
+        assert!(glob_matches("foo*", "foobar"));
+        assert!(glob_matches("*bar", "foobar"));
+        assert!(glob_matches("f?o*r", "foobar"));
+        assert!(!glob_matches("f?o", "fo"));
+        assert!(glob_matches("*", ""));
+    }
+
+    #[test]
+    fn describes_errors_as_json() {
+        use anyhow::Context as _;
+
+        let plain = anyhow::anyhow!("Something went wrong");
+        assert_eq!(
+            error_to_json(&plain),
+            serde_json::json!({
+                "schema": 1,
+                "data": {
+                    "error": "error",
+                    "message": "Something went wrong",
+                    "detail": null,
+                },
+            })
+        );
+
+        let io: Result<()> = Err(std::io::Error::other("disk full").into());
+        let wrapped = io.context("Failed to write file").unwrap_err();
+        assert_eq!(
+            error_to_json(&wrapped),
+            serde_json::json!({
+                "schema": 1,
+                "data": {
+                    "error": "io",
+                    "message": "Failed to write file",
+                    "detail": "disk full",
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn parses_durations() {
+        use std::time::Duration;
+
+        assert_eq!(parse_duration("45s").unwrap(), Duration::from_secs(45));
+        assert_eq!(
+            parse_duration("12h").unwrap(),
+            Duration::from_secs(12 * 3600)
+        );
+        assert_eq!(
+            parse_duration("30d").unwrap(),
+            Duration::from_secs(30 * 86400)
+        );
+        assert_eq!(
+            parse_duration("2w").unwrap(),
+            Duration::from_secs(14 * 86400)
+        );
+        assert!(parse_duration("30").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("1.5d").is_err());
+        assert!(parse_duration("30 days").is_err());
+        assert!(parse_duration("99999999999999999999w").is_err());
+        assert!(parse_duration(&format!("{}w", u64::MAX / 60)).is_err());
+    }
+
+    #[test]
+    fn formats_sizes() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MiB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024 / 2), "1.5 GiB");
+        assert_eq!(format_size(2048 * 1024 * 1024 * 1024 * 1024), "2048.0 TiB");
+    }
+
+    #[test]
+    fn suggests_closest_name() {
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+
+        let names = ["foo".to_owned(), "project".to_owned()];
+        assert_eq!(closest_match("fooo", &names), Some("foo"));
+        assert_eq!(closest_match("projetc", &names), Some("project"));
+        assert_eq!(closest_match("something", &names), None);
+    }
+}
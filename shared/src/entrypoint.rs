@@ -11,6 +11,10 @@ pub struct CommonEntrypointOptions {
     #[arg(long, default_value_t = false)]
     pub root: bool,
 
+    /// Start without the SSH agent, so that no keys can be used.
+    #[arg(long, default_value_t = false)]
+    pub no_agent: bool,
+
     /// Specify what to do with background processes.
     #[arg(long, value_enum, default_value_t = Default::default())]
     pub wait: WaitBehaviour,